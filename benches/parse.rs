@@ -2,23 +2,25 @@ use std::fs;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 use cstree::NodeCache;
-use zaia::parser::parse;
+use zaia::parser::{parse, FileId, ParseOptions, SourceDb};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let source = fs::read_to_string("test-files/mixed.lua").unwrap();
+    let mut db = SourceDb::new();
+    let file = db.add("mixed.lua", source.as_str());
     let mut group = c.benchmark_group("parse");
     let mut deferred = Vec::new();
     group.throughput(Throughput::Elements(source.lines().count() as u64));
     group.bench_function("mixed.lua", |b| {
-        b.iter(|| parse_mixed(black_box(&source), &mut deferred));
+        b.iter(|| parse_mixed(black_box(&db), file, &mut deferred));
     });
 
     group.finish();
 }
 
-fn parse_mixed(source: &str, deferred: &mut Vec<NodeCache<'static>>) {
+fn parse_mixed(db: &SourceDb, file: FileId, deferred: &mut Vec<NodeCache<'static>>) {
     let mut cache = NodeCache::new();
-    parse(&mut cache, source);
+    parse(&mut cache, db, file, ParseOptions::default());
     deferred.push(cache);
 }
 
@@ -0,0 +1,54 @@
+use std::{fs, iter};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+use zaia::parser::{new_shared_interner, parse_all, ParseOptions, SourceDb};
+
+// Each test file is added this many times, so that there is enough work to
+// spread across all cores.
+const COPIES: usize = 64;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut db = SourceDb::new();
+    let mut bytes = 0;
+    for copy in 0..COPIES {
+        for entry in fs::read_dir("test-files").unwrap() {
+            let path = entry.unwrap().path();
+            let source = fs::read_to_string(&path).unwrap();
+            bytes += source.len();
+            db.add(format!("{}/{}", copy, path.display()), source);
+        }
+    }
+
+    let mut group = c.benchmark_group("parse_all");
+    group.throughput(Throughput::Bytes(bytes as u64));
+
+    // Doubles the number of threads until all cores are used.
+    let cores = rayon::current_num_threads();
+    let mut thread_counts: Vec<usize> = iter::successors(Some(1), |threads| Some(threads * 2))
+        .take_while(|&threads| threads < cores)
+        .collect();
+    thread_counts.push(cores);
+
+    for threads in thread_counts {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &db, |b, db| {
+            b.iter_with_large_drop(|| {
+                pool.install(|| {
+                    let interner = new_shared_interner();
+                    let parsed = parse_all(black_box(db), &interner, ParseOptions::default());
+                    (parsed, interner)
+                })
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+#![no_main]
+
+use cstree::NodeCache;
+use libfuzzer_sys::fuzz_target;
+use zaia::parser::{parse, parse_block, parse_expr, parse_stmt, Dialect, ParseOptions, SourceDb};
+
+const DIALECTS: [Dialect; 5] = [
+    Dialect::Lua51,
+    Dialect::Lua52,
+    Dialect::Lua53,
+    Dialect::Lua54,
+    Dialect::LuaJit,
+];
+
+// The first byte selects the dialect and the entry point, the rest is the
+// source.
+fuzz_target!(|data: &[u8]| {
+    let (&selector, source) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    if let Ok(source) = std::str::from_utf8(source) {
+        let options = ParseOptions::new(DIALECTS[usize::from(selector) % DIALECTS.len()]);
+        let mut db = SourceDb::new();
+        let file = db.add("fuzz.lua", source);
+        let mut cache = NodeCache::new();
+        let (tree, _) = match usize::from(selector) / DIALECTS.len() % 4 {
+            0 => parse(&mut cache, &db, file, options),
+            1 => parse_expr(&mut cache, &db, file, options),
+            2 => parse_block(&mut cache, &db, file, options),
+            _ => parse_stmt(&mut cache, &db, file, options),
+        };
+
+        assert_eq!(tree.text(cache.interner()).to_string(), source);
+    }
+});
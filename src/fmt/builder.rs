@@ -0,0 +1,434 @@
+use cstree::{interning::Resolver, NodeOrToken};
+use logos::Logos;
+
+use super::{doc::Doc, FormatConfig, QuoteStyle};
+use crate::{
+    parser::{
+        machinery::{
+            binding_power::{infix_binding_power, prefix_binding_power},
+            kind::SyntaxKind,
+        },
+        syntax::{SyntaxNode, SyntaxToken},
+    },
+    T,
+};
+
+type Element<'a> = NodeOrToken<&'a SyntaxNode, &'a SyntaxToken>;
+
+// What goes between two pieces of output, ordered from weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Sep {
+    None,
+    Space,
+    SoftLine,
+    Line,
+    HardLine,
+    BlankLine,
+}
+
+struct Last<'a> {
+    kind: SyntaxKind,
+    parent: SyntaxKind,
+    text: &'a str,
+}
+
+/// Turns a syntax tree into a document. Separators are decided from the
+/// surrounding tokens and the nodes they belong to, the whitespace in the
+/// source only matters for where comments and blank lines go.
+pub struct Builder<'a, I: ?Sized> {
+    resolver: &'a I,
+    config: &'a FormatConfig,
+    stack: Vec<Vec<Doc>>,
+    // Overrides the separator before the next token.
+    pending: Option<Sep>,
+    // The number of line breaks in the whitespace skipped since the last
+    // token or comment.
+    newlines: usize,
+    last: Option<Last<'a>>,
+    started: bool,
+    last_is_comment: bool,
+    // Set after a line comment, nothing may follow it on the same line.
+    force_newline: bool,
+    // Set right after the token that opens a block.
+    block_start: bool,
+    // Set right after a block, tells whether it was empty.
+    after_block: Option<bool>,
+}
+
+impl<'a, I> Builder<'a, I>
+where
+    I: Resolver + ?Sized,
+{
+    pub fn new(resolver: &'a I, config: &'a FormatConfig) -> Self {
+        Self {
+            resolver,
+            config,
+            stack: vec![Vec::new()],
+            pending: None,
+            newlines: 0,
+            last: None,
+            started: false,
+            last_is_comment: false,
+            force_newline: false,
+            block_start: false,
+            after_block: None,
+        }
+    }
+
+    pub fn build(mut self, root: &'a SyntaxNode) -> Vec<Doc> {
+        self.elements(root);
+        self.stack.pop().unwrap_or_default()
+    }
+
+    fn push(&mut self, doc: Doc) {
+        if let Some(docs) = self.stack.last_mut() {
+            docs.push(doc);
+        }
+    }
+
+    fn begin(&mut self) {
+        self.stack.push(Vec::new());
+    }
+
+    fn end(&mut self, wrap: fn(Vec<Doc>) -> Doc) {
+        let docs = self.stack.pop().unwrap_or_default();
+        self.push(wrap(docs));
+    }
+
+    fn push_sep(&mut self, sep: Sep) {
+        match sep {
+            Sep::None => {},
+            Sep::Space => self.push(Doc::Space),
+            Sep::SoftLine => self.push(Doc::SoftLine),
+            Sep::Line => self.push(Doc::Line),
+            Sep::HardLine => self.push(Doc::HardLine),
+            Sep::BlankLine => {
+                self.push(Doc::HardLine);
+                self.push(Doc::HardLine);
+            },
+        }
+    }
+
+    fn node(&mut self, node: &'a SyntaxNode) {
+        match node.kind() {
+            T![stmt_list] => {
+                self.begin();
+                self.elements(node);
+                self.end(Doc::Indent);
+
+                let empty = node.children_with_tokens().next().is_none();
+                self.after_block = Some(empty && !self.last_is_comment);
+            },
+            T![func_args] | T![table_expr] => self.list(node),
+            _ => self.elements(node),
+        }
+    }
+
+    fn elements(&mut self, node: &'a SyntaxNode) {
+        let elements: Vec<Element> = node.children_with_tokens().collect();
+        let block = matches!(node.kind(), T![root] | T![stmt_list]);
+
+        for (i, &element) in elements.iter().enumerate() {
+            match element {
+                NodeOrToken::Node(child) => {
+                    // Every node in a block is a statement.
+                    if block {
+                        self.statement_sep(child.kind());
+                    }
+
+                    self.node(child);
+                },
+                NodeOrToken::Token(token) => {
+                    let in_block = in_block_region(&elements, i);
+                    self.token_or_trivia(token, in_block);
+                },
+            }
+        }
+    }
+
+    fn token_or_trivia(&mut self, token: &'a SyntaxToken, in_block: bool) {
+        match token.kind() {
+            T![whitespace] => {
+                let text = token.resolve_text(self.resolver);
+                self.newlines += text.matches('\n').count();
+            },
+            T![comment] => self.comment(token, in_block),
+            T![bom] => self.push(Doc::Text(token.resolve_text(self.resolver).to_owned())),
+            T![shebang] => {
+                let text = token.resolve_text(self.resolver).trim_end();
+                self.push(Doc::Text(text.to_owned()));
+                self.started = true;
+                self.force_newline = true;
+                self.newlines = 0;
+            },
+            _ => self.token(token),
+        }
+    }
+
+    fn statement_sep(&mut self, kind: SyntaxKind) {
+        if !self.started {
+            return;
+        }
+
+        // Semicolons stay on the line of the statement they end.
+        let attached = self.newlines == 0 && !self.block_start && !self.last_is_comment;
+        self.pending = Some(if kind == T![;] && attached {
+            Sep::None
+        } else {
+            self.line_sep()
+        });
+    }
+
+    // Starts a new line, keeping a single blank line if there was at least one
+    // in the source. Blocks never start with a blank line.
+    fn line_sep(&self) -> Sep {
+        if self.newlines >= 2 && !self.block_start {
+            Sep::BlankLine
+        } else {
+            Sep::HardLine
+        }
+    }
+
+    // Lays out call arguments, parameter lists and table constructors on one
+    // line if they fit, and with one element per line otherwise.
+    fn list(&mut self, node: &'a SyntaxNode) {
+        let empty = node.children_with_tokens().all(|element| {
+            matches!(
+                element.kind(),
+                T!['('] | T![')'] | T!['{'] | T!['}'] | T![whitespace]
+            )
+        });
+
+        let table = node.kind() == T![table_expr];
+        if empty || (!table && hugs_last_arg(node)) {
+            self.elements(node);
+            return;
+        }
+
+        let inner = if table { Sep::Line } else { Sep::SoftLine };
+        self.begin();
+
+        for element in node.children_with_tokens() {
+            match element {
+                NodeOrToken::Token(token) => match token.kind() {
+                    T!['('] | T!['{'] => {
+                        self.token(token);
+                        self.begin();
+                        self.pending = Some(inner);
+                    },
+                    T![')'] | T!['}'] => {
+                        self.end(Doc::Indent);
+                        self.pending = Some(inner);
+                        self.token(token);
+                    },
+                    T![,] | T![;] => {
+                        self.token(token);
+                        self.pending = Some(Sep::Line);
+                    },
+                    _ => self.token_or_trivia(token, false),
+                },
+                NodeOrToken::Node(child) => self.node(child),
+            }
+        }
+
+        self.end(Doc::Group);
+    }
+
+    fn comment(&mut self, token: &'a SyntaxToken, in_block: bool) {
+        let text = token.resolve_text(self.resolver);
+        let long = is_long_comment(text);
+        let text = if long { text } else { text.trim_end() };
+
+        let mut sep = if !self.started {
+            Sep::None
+        } else if self.newlines == 0 {
+            Sep::Space
+        } else {
+            self.line_sep()
+        };
+
+        if self.force_newline {
+            sep = sep.max(Sep::HardLine);
+        }
+
+        // Comments belonging to the start or the end of a block are indented
+        // along with it.
+        self.pending = None;
+        if in_block {
+            self.begin();
+        }
+
+        self.push_sep(sep);
+        self.push(Doc::Text(text.to_owned()));
+        if in_block {
+            self.end(Doc::Indent);
+        }
+
+        self.started = true;
+        self.last_is_comment = true;
+        self.force_newline = !long;
+        self.block_start = false;
+        self.newlines = 0;
+        if self.after_block.is_some() {
+            self.after_block = Some(false);
+        }
+    }
+
+    fn token(&mut self, token: &'a SyntaxToken) {
+        let kind = token.kind();
+        let parent = token.parent().kind();
+        let text = token.resolve_text(self.resolver);
+
+        let mut sep = match self.pending.take() {
+            Some(sep) => sep,
+            None => self.separator(kind, parent),
+        };
+
+        if self.force_newline {
+            sep = sep.max(Sep::HardLine);
+        }
+
+        if sep == Sep::None && !self.joins_cleanly(kind, text) {
+            sep = Sep::Space;
+        }
+
+        self.push_sep(sep);
+        self.push(Doc::Text(self.quote(kind, text)));
+
+        self.last = Some(Last { kind, parent, text });
+        self.started = true;
+        self.last_is_comment = false;
+        self.force_newline = false;
+        self.block_start = opens_block(token);
+        self.after_block = None;
+        self.newlines = 0;
+    }
+
+    fn separator(&self, kind: SyntaxKind, parent: SyntaxKind) -> Sep {
+        let last = match &self.last {
+            Some(last) => last,
+            None => return Sep::None,
+        };
+
+        // Empty blocks are closed on the same line.
+        if matches!(kind, T![end] | T![until] | T![else] | T![elseif]) {
+            return match self.after_block {
+                Some(true) => Sep::Space,
+                _ => Sep::HardLine,
+            };
+        }
+
+        if self.last_is_comment {
+            return Sep::Space;
+        }
+
+        let tight = match (last.kind, kind) {
+            (_, T![,] | T![;] | T![')'] | T![']'] | T![.] | T![:]) => true,
+            (T!['('] | T!['['] | T![.] | T![:], _) => true,
+            (T!['{'], T!['}']) => true,
+            (_, T!['(']) if parent == T![func_args] => true,
+            (_, T!['[']) if parent == T![index] => true,
+            _ if parent == T![label_stmt] => true,
+            _ if parent == T![attrib] => kind != T![<],
+            _ if last.parent == T![prefix_op] => last.kind != T![not],
+            _ =>
+                binds_tighter_than_unary(kind, parent)
+                    || binds_tighter_than_unary(last.kind, last.parent),
+        };
+
+        if tight {
+            Sep::None
+        } else {
+            Sep::Space
+        }
+    }
+
+    // Checks that the token doesn't merge with the previous one when nothing
+    // separates them, like `-` followed by `-` would start a comment.
+    fn joins_cleanly(&self, kind: SyntaxKind, text: &str) -> bool {
+        let last = match &self.last {
+            Some(last) => last,
+            None => return true,
+        };
+
+        let joined = format!("{}{}", last.text, text);
+        let mut lexer = SyntaxKind::lexer(&joined);
+        let first = lexer.next().map(|first| (first, lexer.span()));
+        let second = lexer.next().map(|second| (second, lexer.span()));
+
+        first == Some((last.kind, 0..last.text.len()))
+            && second == Some((kind, last.text.len()..joined.len()))
+    }
+
+    // Switches short strings to the preferred quotes, as long as they contain
+    // neither kind of quote so that no escapes need to change.
+    fn quote(&self, kind: SyntaxKind, text: &str) -> String {
+        let quote = match self.config.quote_style {
+            QuoteStyle::Double => '"',
+            QuoteStyle::Single => '\'',
+            QuoteStyle::Preserve => return text.to_owned(),
+        };
+
+        if kind != T![string] {
+            return text.to_owned();
+        }
+
+        let inner = &text[1..text.len() - 1];
+        if inner.contains(&['"', '\''][..]) {
+            return text.to_owned();
+        }
+
+        format!("{}{}{}", quote, inner, quote)
+    }
+}
+
+// Comments between a block and the keywords surrounding it end up in the
+// statement's node rather than the block's, but belong to the block.
+fn in_block_region(elements: &[Element], i: usize) -> bool {
+    let significant = |element: &&Element| !element.kind().is_trivia();
+    let is_block = |element: Option<&Element>| {
+        element.map_or(false, |element| element.kind() == T![stmt_list])
+    };
+
+    is_block(elements[..i].iter().rev().find(significant))
+        || is_block(elements[i + 1..].iter().find(significant))
+}
+
+// Calls whose last argument is a function or a table keep the parentheses
+// around it, so that its body is indented relative to the call.
+fn hugs_last_arg(args: &SyntaxNode) -> bool {
+    let call = args.parent().map_or(false, |parent| {
+        matches!(parent.kind(), T![func_call] | T![method_call_expr])
+    });
+
+    let comments = args
+        .children_with_tokens()
+        .any(|element| element.kind() == T![comment]);
+
+    let last = args.children().last().map(|last| last.kind());
+    call && !comments && matches!(last, Some(T![func_expr] | T![table_expr]))
+}
+
+fn opens_block(token: &SyntaxToken) -> bool {
+    match token.kind() {
+        T![do] | T![then] | T![else] | T![repeat] => true,
+        T![')'] => {
+            let function = token.parent().parent().map(SyntaxNode::kind);
+            token.parent().kind() == T![func_args]
+                && matches!(function, Some(T![func_stmt] | T![func_expr]))
+        },
+        _ => false,
+    }
+}
+
+// Operators binding tighter than unary ones, only `^`, are written without
+// spaces around them.
+fn binds_tighter_than_unary(kind: SyntaxKind, parent: SyntaxKind) -> bool {
+    let ((), unary) = prefix_binding_power(T![not]);
+    parent == T![bin_op] && infix_binding_power(kind).map_or(false, |(left, _)| left > unary)
+}
+
+fn is_long_comment(text: &str) -> bool {
+    let rest = &text[2..];
+    rest.starts_with('[') && rest[1..].trim_start_matches('=').starts_with('[')
+}
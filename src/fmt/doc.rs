@@ -0,0 +1,123 @@
+/// Describes the ways a piece of formatted code can be laid out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Doc {
+    Text(String),
+    Space,
+    /// A space, or a line break if the enclosing group doesn't fit on a line.
+    Line,
+    /// Nothing, or a line break if the enclosing group doesn't fit on a line.
+    SoftLine,
+    HardLine,
+    /// Indents all line breaks within by one more level.
+    Indent(Vec<Doc>),
+    /// Lays out its contents on a single line if they fit, and breaks all of
+    /// its lines otherwise. A group containing a hard line break always breaks.
+    Group(Vec<Doc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+// The printer works on a stack of documents still to be printed, along with
+// the indentation level and mode each of them is printed in.
+type Command<'a> = (usize, Mode, &'a Doc);
+
+/// Prints documents, breaking groups that would exceed `line_width`. Trailing
+/// whitespace is removed from every line, and the output ends with exactly one
+/// line break unless it's empty.
+pub fn print(docs: &[Doc], line_width: usize, indent_width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut stack: Vec<Command> = docs.iter().rev().map(|doc| (0, Mode::Break, doc)).collect();
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                out.push_str(text);
+                column = match text.rsplit_once('\n') {
+                    Some((_, last)) => width(last),
+                    None => column + width(text),
+                };
+            },
+            Doc::Space => {
+                out.push(' ');
+                column += 1;
+            },
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                column += 1;
+            },
+            Doc::SoftLine if mode == Mode::Flat => {},
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                let trimmed = out.trim_end_matches(' ').len();
+                out.truncate(trimmed);
+                out.push('\n');
+
+                column = indent * indent_width;
+                out.extend(std::iter::repeat(' ').take(column));
+            },
+            Doc::Indent(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent + 1, mode, doc)));
+            },
+            Doc::Group(docs) => {
+                let remaining = line_width as isize - column as isize;
+                let mode = if mode == Mode::Flat || fits(docs, &stack, remaining) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            },
+        }
+    }
+
+    let len = out.trim_end().len();
+    out.truncate(len);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    out
+}
+
+// Checks whether the group fits into the remaining width when laid out flat,
+// together with whatever follows it up to the next line break.
+fn fits(group: &[Doc], rest: &[Command], mut remaining: isize) -> bool {
+    let mut stack: Vec<(Mode, &Doc)> = group.iter().rev().map(|doc| (Mode::Flat, doc)).collect();
+    let mut rest = rest.iter().rev();
+
+    while remaining >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(command) => command,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => match text.split_once('\n') {
+                Some((first, _)) => return remaining >= width(first) as isize,
+                None => remaining -= width(text) as isize,
+            },
+            Doc::Space => remaining -= 1,
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => remaining -= 1,
+            Doc::SoftLine => {},
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::Indent(docs) | Doc::Group(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc)));
+            },
+        }
+    }
+
+    false
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
@@ -0,0 +1,155 @@
+//! A formatter for Lua source built on the lossless syntax tree. Comments and
+//! blank lines between statements are kept, everything else about the layout
+//! is decided by the formatter.
+
+mod builder;
+mod doc;
+
+use cstree::NodeCache;
+
+use self::builder::Builder;
+use crate::parser::{parse, Dialect, ParseOptions, SourceDb};
+
+/// Which quotes short strings are written with. Strings containing either kind
+/// of quote are always left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+    Preserve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatConfig {
+    pub line_width: usize,
+    pub indent_width: usize,
+    pub quote_style: QuoteStyle,
+    pub dialect: Dialect,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            line_width: 100,
+            indent_width: 4,
+            quote_style: QuoteStyle::Double,
+            dialect: Dialect::default(),
+        }
+    }
+}
+
+/// Formats a Lua file. Source that doesn't parse without errors is returned
+/// unchanged, since its meaning can't be relied on to survive.
+pub fn format(source: &str, config: &FormatConfig) -> String {
+    let mut cache = NodeCache::new();
+    let mut db = SourceDb::new();
+    let file = db.add("input.lua", source);
+    let (root, reports) = parse(&mut cache, &db, file, ParseOptions::new(config.dialect));
+    if !reports.is_empty() {
+        return source.to_owned();
+    }
+
+    let docs = Builder::new(cache.interner(), config).build(&root);
+    doc::print(&docs, config.line_width, config.indent_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cstree::{NodeCache, NodeOrToken};
+
+    use super::{format, FormatConfig, QuoteStyle};
+    use crate::{
+        parser::{decode_string, parse, ParseOptions, SourceDb},
+        T,
+    };
+
+    // The node kinds and significant tokens of a file, which formatting must
+    // not change. Strings are compared by value, since their quotes may change.
+    fn shape(source: &str) -> Vec<String> {
+        let mut cache = NodeCache::new();
+        let mut db = SourceDb::new();
+        let file = db.add("test.lua", source);
+        let (root, reports) = parse(&mut cache, &db, file, ParseOptions::default());
+        assert!(reports.is_empty());
+
+        root.descendants_with_tokens()
+            .filter(|element| !element.kind().is_trivia())
+            .map(|element| match element {
+                NodeOrToken::Node(node) => node.kind().to_string(),
+                NodeOrToken::Token(token) => {
+                    let text = token.resolve_text(cache.interner());
+                    match token.kind() {
+                        T![string] => format!("{} {:?}", token.kind(), decode_string(text)),
+                        kind => format!("{} {}", kind, text),
+                    }
+                },
+            })
+            .collect()
+    }
+
+    fn comments(source: &str) -> Vec<String> {
+        let mut cache = NodeCache::new();
+        let mut db = SourceDb::new();
+        let file = db.add("test.lua", source);
+        let (root, _) = parse(&mut cache, &db, file, ParseOptions::default());
+
+        root.descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+            .filter(|token| token.kind() == T![comment])
+            .map(|token| token.resolve_text(cache.interner()).trim_end().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn format_test_files() {
+        let configs = [
+            FormatConfig::default(),
+            FormatConfig {
+                quote_style: QuoteStyle::Preserve,
+                ..FormatConfig::default()
+            },
+        ];
+
+        for config in configs {
+            for entry in fs::read_dir("test-files").unwrap() {
+                let path = entry.unwrap().path();
+                let name = format!("{} ({:?})", path.display(), config.quote_style);
+                let source = fs::read_to_string(&path).unwrap();
+                let formatted = format(&source, &config);
+
+                assert_eq!(shape(&formatted), shape(&source), "{}", name);
+                assert_eq!(comments(&formatted), comments(&source), "{}", name);
+                assert_eq!(format(&formatted, &config), formatted, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn format_small_snippets() {
+        let config = FormatConfig::default();
+        let cases = [
+            ("local x=1+2*3^-y", "local x = 1 + 2 * 3^-y\n"),
+            ("if a then end", "if a then end\n"),
+            ("while not a do f() end", "while not a do\n    f()\nend\n"),
+            ("x = 'a' .. 'b\"' -- done", "x = \"a\" .. 'b\"' -- done\n"),
+            ("local t = {}", "local t = {}\n"),
+            ("x = - -1", "x = - -1\n"),
+            (
+                "f(function() return 1 end)",
+                "f(function()\n    return 1\nend)\n",
+            ),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(format(source, &config), expected);
+        }
+    }
+
+    #[test]
+    fn keep_invalid_source() {
+        let source = "local = 1";
+        assert_eq!(format(source, &FormatConfig::default()), source);
+    }
+}
@@ -1,7 +1,9 @@
 #![feature(allocator_api)]
 #![feature(cell_update)]
+#![feature(once_cell)]
 #![allow(dead_code)]
 
 mod engine;
+pub mod fmt;
 pub mod parser;
 mod ir;
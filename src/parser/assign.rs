@@ -1,25 +1,50 @@
 use super::{
-    machinery::marker::{CompletedMarker, Marker},
+    diagnostic::{Code, Diagnostic, Label},
+    machinery::{
+        marker::{CompletedMarker, Marker},
+        span::Span,
+    },
+    options::Feature,
     Parser,
 };
 use crate::T;
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_maybe_assign(&mut self) -> Option<CompletedMarker> {
         let assign_marker = self.start();
+        let start = self.span();
         let expr_marker = self.r_simple_expr(true);
         if matches!(self.at(), T![=] | T![,]) {
-            self.r_assign(assign_marker)
-        } else {
-            assign_marker.abandon(self);
-            expr_marker
+            self.check_assign_target(start, expr_marker.as_ref());
+            return self.r_assign(assign_marker);
+        }
+
+        assign_marker.abandon(self);
+        let is_call = matches!(
+            expr_marker.as_ref().map(CompletedMarker::kind),
+            Some(T![func_call] | T![method_call_expr])
+        );
+
+        if expr_marker.is_some() && !is_call {
+            let span = self.span_since(start);
+            let error = Diagnostic::error(
+                Code::NotAStatement,
+                "syntax error: expression is not a statement",
+                Label::new(span, "only calls and assignments can be used as statements"),
+            );
+
+            self.syntax_error(error);
         }
+
+        expr_marker
     }
 
     pub(super) fn r_assign(&mut self, marker: Marker) -> Option<CompletedMarker> {
         while self.at() == T![,] {
             self.expect(T![,]);
-            self.r_simple_expr(true);
+            let start = self.span();
+            let target = self.r_simple_expr(true);
+            self.check_assign_target(start, target.as_ref());
         }
 
         self.expect(T![=]);
@@ -27,6 +52,31 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         Some(marker.complete(self, T![assign_stmt]))
     }
 
+    // Only names, fields and indexed values can be assigned to, a call or a
+    // parenthesised expression can't.
+    fn check_assign_target(&mut self, start: Span, target: Option<&CompletedMarker>) {
+        let kind = match target {
+            Some(target) => target.kind(),
+            None => return,
+        };
+
+        if matches!(kind, T![ident] | T![field_expr] | T![index]) {
+            return;
+        }
+
+        let span = self.span_since(start);
+        let error = Diagnostic::error(
+            Code::InvalidAssignTarget,
+            "syntax error: cannot assign to this expression",
+            Label::new(
+                span,
+                "only names, fields and indexed values can be assigned to",
+            ),
+        );
+
+        self.syntax_error(error);
+    }
+
     pub(super) fn r_decl(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T![local]);
@@ -35,7 +85,6 @@ impl<'cache, 'source> Parser<'cache, 'source> {
             self.r_func(false);
         } else {
             self.r_decl_target();
-
             while self.at() == T![,] {
                 self.expect(T![,]);
                 self.r_decl_target();
@@ -57,11 +106,34 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         Some(marker.complete(self, T![decl_target]))
     }
 
-    fn r_attrib(&mut self) {
-        let t = self.at();
+    fn r_attrib(&mut self) -> Option<CompletedMarker> {
+        if self.at() != T![<] {
+            return None;
+        }
 
-        if matches!(t, T![const] | T![close]) {
-            self.expect(t);
+        let marker = self.start();
+        self.expect(T![<]);
+        if self.at() == T![ident] {
+            self.require(Feature::Attributes);
+            match self.source(self.span()) {
+                "const" | "close" => {},
+                attrib => {
+                    let error = Diagnostic::error(
+                        Code::UnknownAttribute,
+                        "unknown attribute",
+                        self.new_label(format!(
+                            "unknown attribute \"{}\", expected \"const\" or \"close\"",
+                            attrib
+                        )),
+                    );
+
+                    self.report(error);
+                },
+            }
         }
+
+        self.expect(T![ident]);
+        self.expect(T![>]);
+        Some(marker.complete(self, T![attrib]))
     }
 }
@@ -0,0 +1,51 @@
+use cstree::NodeCache;
+use fxhash::FxBuildHasher;
+use lasso::{Spur, ThreadedRodeo};
+use rayon::prelude::*;
+
+use super::{
+    diagnostic::Diagnostic,
+    options::ParseOptions,
+    parse,
+    source::{FileId, SourceDb},
+    syntax::SyntaxNode,
+};
+
+/// An interner that any number of threads can intern into at the same time.
+/// Trees parsed with it are resolved with it as well.
+pub type SharedInterner = ThreadedRodeo<Spur, FxBuildHasher>;
+
+pub fn new_shared_interner() -> SharedInterner {
+    ThreadedRodeo::with_hasher(FxBuildHasher::default())
+}
+
+/// The tree and reports for one file of a batch.
+#[derive(Debug)]
+pub struct ParsedFile {
+    pub file: FileId,
+    pub tree: SyntaxNode,
+    pub reports: Vec<Diagnostic>,
+}
+
+/// Parses every file of the database on rayon's thread pool. Token texts from
+/// all files end up in `interner`. The results are in the order of
+/// [`SourceDb::files`].
+pub fn parse_all(
+    db: &SourceDb,
+    interner: &SharedInterner,
+    options: ParseOptions,
+) -> Vec<ParsedFile> {
+    let files: Vec<FileId> = db.files().collect();
+    files
+        .into_par_iter()
+        .map(|file| {
+            let mut cache = NodeCache::from_interner(interner);
+            let (tree, reports) = parse(&mut cache, db, file, options);
+            ParsedFile {
+                file,
+                tree,
+                reports,
+            }
+        })
+        .collect()
+}
@@ -1,17 +1,19 @@
 use super::{
     machinery::{
+        classifiers::{token_is_expr_start, token_is_stmt_start},
         kind::SyntaxKind,
         marker::{CompletedMarker, Marker},
     },
+    options::Feature,
     Parser,
 };
 use crate::T;
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_do(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T![do]);
-        self.r_block(|t| t == T![end]);
+        self.r_block(&[T![end]]);
         self.expect(T![end]);
         Some(marker.complete(self, T![block_stmt]))
     }
@@ -27,7 +29,7 @@ impl<'cache, 'source> Parser<'cache, 'source> {
     pub(super) fn r_repeat(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T![repeat]);
-        self.r_block(|t| t == T![until]);
+        self.r_block(&[T![until]]);
         self.expect(T![until]);
         self.r_expr();
         Some(marker.complete(self, T![repeat_stmt]))
@@ -38,7 +40,7 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         self.expect(if_kind);
         self.r_expr();
         self.expect(T![then]);
-        self.r_block(|t| matches!(t, T![end] | T![elseif] | T![else]));
+        self.r_block(&[T![end], T![elseif], T![else]]);
 
         match self.at() {
             T![end] => {
@@ -47,7 +49,9 @@ impl<'cache, 'source> Parser<'cache, 'source> {
             T![elseif] | T![else] => {
                 self.r_else();
             },
-            _ => unreachable!(),
+            _ => {
+                self.expect(T![end]);
+            },
         }
 
         Some(marker.complete(self, T![if_stmt]))
@@ -59,7 +63,7 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         match self.at() {
             T![else] => {
                 self.expect(T![else]);
-                self.r_block(|t| t == T![end]);
+                self.r_block(&[T![end]]);
                 self.expect(T![end]);
             },
             T![elseif] => {
@@ -112,7 +116,10 @@ impl<'cache, 'source> Parser<'cache, 'source> {
     pub(super) fn r_return(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T![return]);
-        self.r_expr_list();
+        if token_is_expr_start(self.at()) {
+            self.r_expr_list();
+        }
+
         Some(marker.complete(self, T![return_stmt]))
     }
 
@@ -122,15 +129,44 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         Some(marker.complete(self, T![break_stmt]))
     }
 
-    pub(super) fn r_block<F>(&mut self, stop: F) -> Option<CompletedMarker>
-    where
-        F: Fn(SyntaxKind) -> bool,
-    {
+    pub(super) fn r_goto(&mut self) -> Option<CompletedMarker> {
+        let marker = self.start();
+        self.expect(T![goto]);
+        if self.at() == T![ident] {
+            let target = self.span();
+            self.add_goto(target);
+        }
+
+        self.expect(T![ident]);
+        Some(marker.complete(self, T![goto_stmt]))
+    }
+
+    pub(super) fn r_label(&mut self) -> Option<CompletedMarker> {
+        let marker = self.start();
+        self.require(Feature::Goto);
+        self.expect(T![::]);
+        if self.at() == T![ident] {
+            let name = self.span();
+            self.add_label(name);
+        }
+
+        self.expect(T![ident]);
+        self.expect(T![::]);
+        Some(marker.complete(self, T![label_stmt]))
+    }
+
+    // Parses statements until one of the tokens ending the block, or a token
+    // an enclosing construct can resume at, is reached.
+    pub(super) fn r_block(&mut self, stop: &'static [SyntaxKind]) -> Option<CompletedMarker> {
         let marker = self.start();
-        while !stop(self.at()) {
+        self.enter_label_scope(false);
+        self.enter_recovery(stop);
+        while !self.at_recovery() || token_is_stmt_start(self.at()) {
             self.r_stmt();
         }
 
+        self.exit_recovery();
+        self.exit_label_scope();
         Some(marker.complete(self, T![stmt_list]))
     }
 }
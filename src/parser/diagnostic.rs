@@ -0,0 +1,211 @@
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+use super::{machinery::span::Span, source::SourceDb};
+
+macro_rules! codes {
+    ($($variant:ident = $code:literal, $description:literal;)*) => {
+        /// Identifies the kind of a [`Diagnostic`]. Codes are stable, a code is
+        /// never reused for a different kind of problem.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum Code {
+            $($variant,)*
+        }
+
+        impl Code {
+            pub const ALL: &'static [Code] = &[$(Code::$variant,)*];
+
+            /// The code as printed, like `E0001`.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Code::$variant => $code,)*
+                }
+            }
+
+            /// A short description of the kind of problem.
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(Code::$variant => $description,)*
+                }
+            }
+        }
+    };
+}
+
+codes! {
+    UnexpectedToken = "E0001", "unexpected token";
+    InvalidToken = "E0002", "invalid token";
+    InvalidString = "E0003", "invalid string literal";
+    ExpectedStatement = "E0004", "expected a statement";
+    ExpectedExpression = "E0005", "expected an expression";
+    ExpectedParameter = "E0006", "expected a parameter";
+    ExpectedTableElement = "E0007", "expected a table element";
+    ExpectedEndOfInput = "E0008", "expected the end of the input";
+    NotAStatement = "E0009", "expression is not a statement";
+    InvalidAssignTarget = "E0010", "cannot assign to this expression";
+    UnsupportedSyntax = "E0011", "syntax not available in the dialect";
+    UnknownAttribute = "E0012", "unknown attribute";
+    UndefinedLabel = "E0013", "undefined label";
+    DuplicateLabel = "E0014", "duplicate label";
+    MultipleClose = "E0015", "multiple to-be-closed variables";
+    BreakOutsideLoop = "E0016", "break outside a loop";
+    StatementAfterReturn = "E0017", "statement after return";
+    AssignToConst = "E0018", "assignment to a const variable";
+    VarArgOutsideVarArgFunction = "E0019", "vararg outside a vararg function";
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// A span with a message explaining its part in a [`Diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// A change to the source that fixes the problem, replacing the text covered
+/// by `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Suggestion {
+    pub fn new(message: impl Into<String>, span: Span, replacement: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+        }
+    }
+}
+
+/// A problem found in a file. The `primary` label points at the problem itself,
+/// `secondary` labels at other places involved in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: Code,
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(code: Code, severity: Severity, message: impl Into<String>, primary: Label) -> Self {
+        Self {
+            code,
+            severity,
+            message: message.into(),
+            primary,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(code: Code, message: impl Into<String>, primary: Label) -> Self {
+        Self::new(code, Severity::Error, message, primary)
+    }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.secondary.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        self.primary.span
+    }
+
+    /// Converts the diagnostic into an ariadne report. Notes and suggestions
+    /// are combined into the note of the report.
+    pub fn to_report(&self) -> ariadne::Report<Span> {
+        let kind = match self.severity {
+            Severity::Error => ariadne::ReportKind::Error,
+            Severity::Warning => ariadne::ReportKind::Warning,
+            Severity::Note => ariadne::ReportKind::Advice,
+        };
+
+        let span = self.span();
+        let mut report = ariadne::Report::build(kind, span.file(), span.start() as usize)
+            .with_code(self.code)
+            .with_message(&self.message)
+            .with_label(ariadne::Label::new(span).with_message(&self.primary.message));
+
+        for label in &self.secondary {
+            report =
+                report.with_label(ariadne::Label::new(label.span).with_message(&label.message));
+        }
+
+        let suggestions = self.suggestions.iter().map(|suggestion| {
+            if suggestion.replacement.is_empty() {
+                format!("help: {}", suggestion.message)
+            } else {
+                format!("help: {}: `{}`", suggestion.message, suggestion.replacement)
+            }
+        });
+
+        let notes: Vec<String> = self.notes.iter().cloned().chain(suggestions).collect();
+        if !notes.is_empty() {
+            report = report.with_note(notes.join("\n"));
+        }
+
+        report.finish()
+    }
+
+    /// Renders the diagnostic with ariadne.
+    pub fn write(&self, db: &SourceDb, w: impl io::Write) -> io::Result<()> {
+        self.to_report().write(db, w)
+    }
+
+    /// Renders the diagnostic with ariadne to stderr.
+    pub fn eprint(&self, db: &SourceDb) -> io::Result<()> {
+        self.to_report().eprint(db)
+    }
+}
@@ -0,0 +1,252 @@
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use super::{
+    diagnostic::{Code, Diagnostic, Label},
+    line_index::LineCol,
+    machinery::span::Span,
+    source::SourceDb,
+};
+
+/// The formats diagnostics can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Rendered by ariadne, with the source and labels drawn below the message.
+    Human,
+    /// One JSON object per line and diagnostic.
+    JsonLines,
+    /// A single SARIF 2.1.0 log.
+    Sarif,
+    /// One line per diagnostic and label, `file:line:col: error: message`.
+    Gcc,
+}
+
+/// Writes diagnostics for files of `db` in the given format.
+pub fn emit(
+    format: Format,
+    db: &SourceDb,
+    diagnostics: &[Diagnostic],
+    mut w: impl Write,
+) -> io::Result<()> {
+    match format {
+        Format::Human => {
+            for diagnostic in diagnostics {
+                diagnostic.write(db, &mut w)?;
+            }
+
+            Ok(())
+        },
+        Format::JsonLines => {
+            for diagnostic in diagnostics {
+                writeln!(w, "{}", json_diagnostic(db, diagnostic))?;
+            }
+
+            Ok(())
+        },
+        Format::Sarif => writeln!(w, "{:#}", sarif_log(db, diagnostics)),
+        Format::Gcc => {
+            for diagnostic in diagnostics {
+                write_gcc(db, diagnostic, &mut w)?;
+            }
+
+            Ok(())
+        },
+    }
+}
+
+// Lines and columns are one-based in all formats. Columns count bytes, except
+// in SARIF, which counts UTF-16 code units by default.
+fn position(position: LineCol) -> (u32, u32) {
+    (position.line + 1, position.col + 1)
+}
+
+fn json_diagnostic(db: &SourceDb, diagnostic: &Diagnostic) -> Value {
+    let label = |label: &Label| {
+        json!({
+            "location": json_location(db, label.span),
+            "message": label.message,
+        })
+    };
+
+    let suggestions: Vec<Value> = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            json!({
+                "message": suggestion.message,
+                "location": json_location(db, suggestion.span),
+                "replacement": suggestion.replacement,
+            })
+        })
+        .collect();
+
+    json!({
+        "code": diagnostic.code.as_str(),
+        "severity": diagnostic.severity.to_string(),
+        "message": diagnostic.message,
+        "primary": label(&diagnostic.primary),
+        "secondary": diagnostic.secondary.iter().map(label).collect::<Vec<_>>(),
+        "notes": diagnostic.notes,
+        "suggestions": suggestions,
+    })
+}
+
+fn json_location(db: &SourceDb, span: Span) -> Value {
+    let range = db.line_index(span.file()).range(span);
+    let (start_line, start_column) = position(range.start);
+    let (end_line, end_column) = position(range.end);
+
+    json!({
+        "file": db.name(span.file()),
+        "start": { "line": start_line, "column": start_column },
+        "end": { "line": end_line, "column": end_column },
+        "offsets": [span.start(), span.end()],
+    })
+}
+
+fn sarif_log(db: &SourceDb, diagnostics: &[Diagnostic]) -> Value {
+    let rules: Vec<Value> = Code::ALL
+        .iter()
+        .map(|code| {
+            json!({
+                "id": code.as_str(),
+                "shortDescription": { "text": code.description() },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| sarif_result(db, diagnostic))
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_result(db: &SourceDb, diagnostic: &Diagnostic) -> Value {
+    let rule_index = Code::ALL
+        .iter()
+        .position(|code| *code == diagnostic.code)
+        .unwrap();
+
+    let primary = &diagnostic.primary;
+    let related: Vec<Value> = diagnostic
+        .secondary
+        .iter()
+        .enumerate()
+        .map(|(id, label)| {
+            json!({
+                "id": id,
+                "physicalLocation": sarif_location(db, label.span),
+                "message": { "text": label.message },
+            })
+        })
+        .collect();
+
+    let fixes: Vec<Value> = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            json!({
+                "description": { "text": suggestion.message },
+                "artifactChanges": [{
+                    "artifactLocation": { "uri": db.name(suggestion.span.file()) },
+                    "replacements": [{
+                        "deletedRegion": sarif_region(db, suggestion.span),
+                        "insertedContent": { "text": suggestion.replacement },
+                    }],
+                }],
+            })
+        })
+        .collect();
+
+    json!({
+        "ruleId": diagnostic.code.as_str(),
+        "ruleIndex": rule_index,
+        "level": diagnostic.severity.to_string(),
+        "message": { "text": diagnostic.message },
+        "locations": [{
+            "physicalLocation": sarif_location(db, primary.span),
+            "message": { "text": primary.message },
+        }],
+        "relatedLocations": related,
+        "fixes": fixes,
+        "properties": { "notes": diagnostic.notes },
+    })
+}
+
+fn sarif_location(db: &SourceDb, span: Span) -> Value {
+    json!({
+        "artifactLocation": { "uri": db.name(span.file()) },
+        "region": sarif_region(db, span),
+    })
+}
+
+fn sarif_region(db: &SourceDb, span: Span) -> Value {
+    let range = db.line_index(span.file()).range_utf16(span);
+    let (start_line, start_column) = position(range.start);
+    let (end_line, end_column) = position(range.end);
+
+    json!({
+        "startLine": start_line,
+        "startColumn": start_column,
+        "endLine": end_line,
+        "endColumn": end_column,
+        "byteOffset": span.start(),
+        "byteLength": span.end() - span.start(),
+    })
+}
+
+// Secondary labels, notes and suggestions follow the diagnostic as notes, so
+// that every line can be read on its own.
+fn write_gcc(db: &SourceDb, diagnostic: &Diagnostic, w: &mut impl Write) -> io::Result<()> {
+    let span = diagnostic.span();
+    writeln!(
+        w,
+        "{}: {}: {} [{}]",
+        gcc_location(db, span),
+        diagnostic.severity,
+        diagnostic.message,
+        diagnostic.code
+    )?;
+
+    for label in &diagnostic.secondary {
+        writeln!(
+            w,
+            "{}: note: {}",
+            gcc_location(db, label.span),
+            label.message
+        )?;
+    }
+
+    for note in &diagnostic.notes {
+        writeln!(w, "{}: note: {}", gcc_location(db, span), note)?;
+    }
+
+    for suggestion in &diagnostic.suggestions {
+        let location = gcc_location(db, suggestion.span);
+        writeln!(w, "{}: note: {}", location, suggestion.message)?;
+    }
+
+    Ok(())
+}
+
+fn gcc_location(db: &SourceDb, span: Span) -> String {
+    let (line, column) = position(db.line_index(span.file()).line_col(span.start()));
+    format!("{}:{}:{}", db.name(span.file()), line, column)
+}
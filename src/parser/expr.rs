@@ -1,33 +1,41 @@
-use super::{machinery::marker::CompletedMarker, Parser};
+use super::{
+    diagnostic::{Code, Diagnostic, Label},
+    literal::{validate_string, LiteralError},
+    machinery::{marker::CompletedMarker, span::Span},
+    options::Feature,
+    Parser,
+};
 use crate::{
     parser::machinery::{
         binding_power::{
             infix_binding_power,
             prefix_binding_power,
             CALL_BINDING_POWER,
+            FIELD_BINDING_POWER,
             INDEX_BINDING_POWER,
         },
-        classifiers::{token_is_expr_start, token_is_literal, token_is_unary_op},
+        classifiers::{
+            node_is_prefix_expr,
+            token_is_bitwise_op,
+            token_is_call_args_start,
+            token_is_literal,
+            token_is_unary_op,
+        },
     },
     T,
 };
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_expr_list(&mut self) {
         let marker = self.start();
-        let mut count = 0;
+        self.r_expr();
 
-        while token_is_expr_start(self.at()) {
-            count += 1;
-            self.r_expr();
-            if self.at() != T![,] {
-                break;
+        if self.at() == T![,] {
+            while self.at() == T![,] {
+                self.expect(T![,]);
+                self.r_expr();
             }
 
-            self.expect(T![,]);
-        }
-
-        if count > 1 {
             marker.complete(self, T![expr_list]);
         } else {
             marker.abandon(self);
@@ -43,15 +51,16 @@ impl<'cache, 'source> Parser<'cache, 'source> {
 
         loop {
             let t = self.at();
+            let prefix = node_is_prefix_expr(lhs.kind());
 
-            if t == T!['('] && CALL_BINDING_POWER >= min_bp {
+            if token_is_call_args_start(t) && prefix && CALL_BINDING_POWER >= min_bp {
                 let n = lhs.precede(self);
                 let _rhs = self.r_func_call_args()?;
                 lhs = n.complete(self, T![func_call]);
                 continue;
             }
 
-            if t == T!['['] && INDEX_BINDING_POWER >= min_bp {
+            if t == T!['['] && prefix && INDEX_BINDING_POWER >= min_bp {
                 let n = lhs.precede(self);
                 self.expect(T!['[']);
                 let _rhs = self.r_expr()?;
@@ -60,11 +69,27 @@ impl<'cache, 'source> Parser<'cache, 'source> {
                 continue;
             }
 
+            if t == T![.] && prefix && FIELD_BINDING_POWER >= min_bp {
+                lhs = self.r_field(lhs);
+                continue;
+            }
+
+            if t == T![:] && prefix && CALL_BINDING_POWER >= min_bp {
+                lhs = self.r_method_call(lhs)?;
+                continue;
+            }
+
             if let Some((l_bp, r_bp)) = infix_binding_power(t) {
                 if l_bp < min_bp {
                     break;
                 }
 
+                if t == T![D/] {
+                    self.require(Feature::IntegerDivision);
+                } else if token_is_bitwise_op(t) {
+                    self.require(Feature::BitwiseOperators);
+                }
+
                 let n = lhs.precede(self);
                 self.expect(t);
                 let _rhs = self.r_expr_inner(r_bp);
@@ -78,6 +103,21 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         Some(lhs)
     }
 
+    pub(super) fn r_field(&mut self, lhs: CompletedMarker) -> CompletedMarker {
+        let n = lhs.precede(self);
+        self.expect(T![.]);
+        self.expect(T![ident]);
+        n.complete(self, T![field_expr])
+    }
+
+    pub(super) fn r_method_call(&mut self, lhs: CompletedMarker) -> Option<CompletedMarker> {
+        let n = lhs.precede(self);
+        self.expect(T![:]);
+        self.expect(T![ident]);
+        let _args = self.r_func_call_args()?;
+        Some(n.complete(self, T![method_call_expr]))
+    }
+
     fn r_expr_lhs(&mut self) -> Option<CompletedMarker> {
         match self.at() {
             T![ident] => self.r_ident(),
@@ -85,15 +125,29 @@ impl<'cache, 'source> Parser<'cache, 'source> {
             T!['{'] => self.r_table(),
             T!['('] => self.r_paren(),
             T![function] => self.r_func(true),
+            T![invalid] => self.r_invalid(),
             t if token_is_unary_op(t) => self.r_expr_unary(),
             t if token_is_literal(t) => self.r_literal(),
-            _ => None,
+            _ => {
+                let error = Diagnostic::error(
+                    Code::ExpectedExpression,
+                    "expected an expression",
+                    self.new_label(format!("expected an expression but found {}", self.at())),
+                );
+
+                self.syntax_error(error);
+                Some(self.missing(None))
+            },
         }
     }
 
     fn r_expr_unary(&mut self) -> Option<CompletedMarker> {
         let n = self.start();
         let op = self.at();
+        if op == T![~] {
+            self.require(Feature::BitwiseOperators);
+        }
+
         self.expect(op);
         let ((), r_bp) = prefix_binding_power(op);
         let _rhs = self.r_expr_inner(r_bp);
@@ -112,7 +166,7 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         Some(marker.complete(self, T![vararg_expr]))
     }
 
-    fn r_paren(&mut self) -> Option<CompletedMarker> {
+    pub(super) fn r_paren(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T!['(']);
         let _rhs = self.r_expr()?;
@@ -120,10 +174,44 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         Some(marker.complete(self, T![expr]))
     }
 
-    fn r_literal(&mut self) -> Option<CompletedMarker> {
+    // Invalid tokens have already been reported by the lexer.
+    pub(super) fn r_invalid(&mut self) -> Option<CompletedMarker> {
+        let marker = self.start();
+        self.expect(T![invalid]);
+        Some(marker.complete(self, T![invalid]))
+    }
+
+    pub(super) fn r_literal(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         let kind = self.at();
+        let suffixed = self.source(self.span()).ends_with(&['l', 'L'][..]);
+        if matches!(kind, T![int] | T![hex_int]) && suffixed {
+            self.require(Feature::IntegerSuffixes);
+        }
+
+        if matches!(kind, T![string] | T![long_string]) {
+            self.check_string();
+        }
+
         self.expect(kind);
         Some(marker.complete(self, T![literal_expr]))
     }
+
+    // Reports every invalid escape sequence in the current string literal.
+    fn check_string(&mut self) {
+        let span = self.span();
+        let errors = match validate_string(self.source(span)) {
+            Ok(_) => return,
+            Err(errors) => errors,
+        };
+
+        for LiteralError { range, message } in errors {
+            let start = span.start() + range.start as u32;
+            let end = span.start() + range.end as u32;
+            let span = Span::new(span.file(), start, end);
+            let error = Diagnostic::error(Code::InvalidString, message, Label::new(span, message));
+
+            self.report(error);
+        }
+    }
 }
@@ -1,30 +1,50 @@
-use super::{machinery::marker::CompletedMarker, Parser};
+use super::{
+    diagnostic::{Code, Diagnostic},
+    machinery::marker::CompletedMarker,
+    stmt::STATEMENT_RECOVERY,
+    Parser,
+};
 use crate::T;
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_func_call_args(&mut self) -> Option<CompletedMarker> {
+        match self.at() {
+            T![string] | T![long_string] => {
+                let marker = self.start();
+                self.r_literal();
+                Some(marker.complete(self, T![string_arg]))
+            },
+            T!['{'] => {
+                let marker = self.start();
+                self.r_table();
+                Some(marker.complete(self, T![table_arg]))
+            },
+            _ => self.r_func_call_paren_args(),
+        }
+    }
+
+    fn r_func_call_paren_args(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T!['(']);
+        self.enter_recovery(&[T![,], T![')']]);
 
-        loop {
-            match self.at() {
-                T![')'] => {
-                    self.expect(T![')']);
+        if self.at() != T![')'] {
+            loop {
+                self.r_expr();
+                if !self.at_recovery() {
+                    self.error_recover(&[T![,], T![')']]);
+                }
+
+                if self.at() != T![,] {
                     break;
-                },
-                _ => {
-                    self.r_expr();
-                },
-            }
+                }
 
-            if self.at() == T![,] {
                 self.expect(T![,]);
-            } else {
-                self.expect(T![')']);
-                break;
             }
         }
 
+        self.exit_recovery();
+        self.expect(T![')']);
         Some(marker.complete(self, T![func_args]))
     }
 
@@ -37,7 +57,9 @@ impl<'cache, 'source> Parser<'cache, 'source> {
         }
 
         self.r_func_def_args();
-        self.r_block(|t| t == T![end]);
+        self.enter_label_scope(true);
+        self.r_block(&[T![end]]);
+        self.exit_label_scope();
         self.expect(T![end]);
         let kind = if expr { T![func_expr] } else { T![func_stmt] };
         Some(marker.complete(self, kind))
@@ -46,30 +68,46 @@ impl<'cache, 'source> Parser<'cache, 'source> {
     fn r_func_def_args(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T!['(']);
+        self.enter_recovery(&[T![,], T![')']]);
 
-        loop {
-            match self.at() {
-                T![')'] => {
-                    self.expect(T![')']);
+        if self.at() != T![')'] {
+            loop {
+                match self.at() {
+                    T![...] => {
+                        self.expect(T![...]);
+                    },
+                    T![ident] => {
+                        self.expect(T![ident]);
+                    },
+                    _ => {
+                        let error = Diagnostic::error(
+                            Code::ExpectedParameter,
+                            "expected a parameter",
+                            self.new_label(format!(
+                                "expected a parameter name or ... but found {}",
+                                self.at()
+                            )),
+                        );
+
+                        self.syntax_error(error);
+                        self.error_eat_until(STATEMENT_RECOVERY);
+                    },
+                }
+
+                if !self.at_recovery() {
+                    self.error_recover(&[T![,], T![')']]);
+                }
+
+                if self.at() != T![,] {
                     break;
-                },
-                T![...] => {
-                    self.expect(T![...]);
-                },
-                T![ident] => {
-                    self.expect(T![ident]);
-                },
-                _ => unreachable!(),
-            }
+                }
 
-            if self.at() == T![,] {
                 self.expect(T![,]);
-            } else {
-                self.expect(T![')']);
-                break;
             }
         }
 
+        self.exit_recovery();
+        self.expect(T![')']);
         Some(marker.complete(self, T![func_args]))
     }
 }
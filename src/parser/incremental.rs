@@ -0,0 +1,271 @@
+use std::{iter, ops::Range};
+
+use cstree::{interning::Interner, GreenNode, NodeCache};
+use logos::Logos;
+
+use super::{
+    diagnostic::Diagnostic,
+    machinery::{
+        kind::{lex_header, SyntaxKind},
+        span::Span,
+    },
+    options::ParseOptions,
+    source::FileId,
+    syntax::SyntaxNode,
+    validate,
+    Parser,
+};
+use crate::T;
+
+/// Replaces the text covered by `span` in the old source with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span, text: impl Into<String>) -> Self {
+        Self {
+            span,
+            text: text.into(),
+        }
+    }
+}
+
+/// Reparses `old_text` after applying `edits` to it, reusing the parts of
+/// `old_tree` that the edits didn't touch.
+///
+/// Only the smallest block that strictly contains all edits is relexed and
+/// reparsed. Whenever that can't be done without changing the outcome, for
+/// example because the block now contains errors, this falls back to a full
+/// parse. The returned tree and reports are always identical to the ones
+/// `parse` would produce for the new text.
+///
+/// Edits are given in terms of the old text and must not overlap. `old_reports`
+/// are the reports for `old_tree`, as returned by `parse` or a previous
+/// reparse. The interner of `cache` has to be the one `old_tree` was built
+/// with, and `options` the ones it was parsed with.
+pub fn reparse<I>(
+    cache: &mut NodeCache<'_, I>,
+    file: FileId,
+    old_tree: &SyntaxNode,
+    old_reports: &[Diagnostic],
+    old_text: &str,
+    edits: &[TextEdit],
+    options: ParseOptions,
+) -> (SyntaxNode, Vec<Diagnostic>)
+where
+    I: Interner,
+{
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| edit.span);
+    let new_text = apply_edits(old_text, &edits);
+    let delta = new_text.len() as i64 - old_text.len() as i64;
+
+    if let Some((tree, block)) =
+        reparse_block(cache, file, old_tree, old_text, &new_text, &edits, options)
+    {
+        if let Some(mut reports) = shift_reports(old_reports, block, delta) {
+            reports.extend(validate::validate(&tree, cache.interner(), file));
+            return (tree, reports);
+        }
+    }
+
+    Parser::new(file, &new_text, options).run(cache, Parser::r_items)
+}
+
+// Carries the reports of the parser over from the old tree, moving the ones
+// following the reparsed block along with its end. Reports of the validator
+// are left out, since it runs over the whole new tree again. Returns nothing
+// if a report involves the block, which might not hold anymore.
+fn shift_reports(reports: &[Diagnostic], block: Range<u32>, delta: i64) -> Option<Vec<Diagnostic>> {
+    let shift = |offset: u32| {
+        if offset <= block.start {
+            Some(offset)
+        } else if offset >= block.end {
+            Some((offset as i64 + delta) as u32)
+        } else {
+            None
+        }
+    };
+
+    let mut shifted = Vec::with_capacity(reports.len());
+    for report in reports {
+        if validate::CODES.contains(&report.code) {
+            continue;
+        }
+
+        let mut report = report.clone();
+        let spans = iter::once(&mut report.primary.span)
+            .chain(report.secondary.iter_mut().map(|label| &mut label.span))
+            .chain(report.suggestions.iter_mut().map(|fix| &mut fix.span));
+
+        for span in spans {
+            *span = Span::new(span.file(), shift(span.start())?, shift(span.end())?);
+        }
+
+        shifted.push(report);
+    }
+
+    Some(shifted)
+}
+
+fn apply_edits(text: &str, edits: &[&TextEdit]) -> String {
+    let mut new_text = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for edit in edits {
+        debug_assert!(edit.span.start() as usize >= last, "overlapping text edits");
+        new_text.push_str(&text[last..edit.span.start() as usize]);
+        new_text.push_str(&edit.text);
+        last = edit.span.end() as usize;
+    }
+
+    new_text.push_str(&text[last..]);
+    new_text
+}
+
+fn reparse_block<I>(
+    cache: &mut NodeCache<'_, I>,
+    file: FileId,
+    old_tree: &SyntaxNode,
+    old_text: &str,
+    new_text: &str,
+    edits: &[&TextEdit],
+    options: ParseOptions,
+) -> Option<(SyntaxNode, Range<u32>)>
+where
+    I: Interner,
+{
+    let start = edits.first()?.span.start();
+    let end = edits.last()?.span.end();
+    let block = enclosing_block(old_tree, start, end)?;
+    let stop = block_stop(block.parent()?.kind())?;
+
+    let range = block.text_range();
+    let delta = edits
+        .iter()
+        .map(|edit| edit.text.len() as i64 - edit.span.range().len() as i64)
+        .sum::<i64>();
+
+    let block_start = u32::from(range.start()) as usize;
+    let old_block_end = u32::from(range.end()) as usize;
+    let new_block_end = (old_block_end as i64 + delta) as usize;
+    if !same_boundary(
+        old_text,
+        old_block_end,
+        new_text,
+        block_start..new_block_end,
+    ) {
+        return None;
+    }
+
+    // A block starting with something that looks like a file header must not be
+    // lexed as one.
+    let source = &new_text[block_start..new_block_end];
+    if !lex_header(source).is_empty() {
+        return None;
+    }
+
+    let green = Parser::new(file, source, options).run_block(stop, cache)?;
+    let root = SyntaxNode::new_root(green);
+    if root.children_with_tokens().count() != 1 {
+        return None;
+    }
+
+    let new_block = root.children().next()?.green().clone();
+    let tree = SyntaxNode::new_root(block.replace_with(new_block));
+    Some((tree, block_start as u32..old_block_end as u32))
+}
+
+// Finds the innermost statement list whose text strictly contains the range.
+fn enclosing_block(node: &SyntaxNode, start: u32, end: u32) -> Option<&SyntaxNode> {
+    let child = node.children().find(|child| {
+        let range = child.text_range();
+        u32::from(range.start()) <= start && end <= u32::from(range.end())
+    });
+
+    let inner = child.and_then(|child| enclosing_block(child, start, end));
+    let range = node.text_range();
+    let strictly_contains = u32::from(range.start()) < start && end < u32::from(range.end());
+
+    inner.or_else(|| (node.kind() == T![stmt_list] && strictly_contains).then(|| node))
+}
+
+// The tokens a block is terminated by, depending on the construct it belongs
+// to.
+fn block_stop(parent: SyntaxKind) -> Option<&'static [SyntaxKind]> {
+    match parent {
+        T![block_stmt] | T![else_chain] | T![func_stmt] | T![func_expr] => Some(&[T![end]]),
+        T![repeat_stmt] => Some(&[T![until]]),
+        T![if_stmt] => Some(&[T![end], T![elseif], T![else]]),
+        _ => None,
+    }
+}
+
+// Checks that the edited block still ends on the same token boundary, i.e. its
+// last token doesn't run into the text following it.
+fn same_boundary(old_text: &str, old_end: usize, new_text: &str, new_range: Range<usize>) -> bool {
+    let old_tail = lex_tail(&old_text[old_end..]);
+    let tail_len: usize = old_tail.iter().map(|(_, len)| len).sum();
+    let boundary = new_range.len();
+
+    let mut lexer = SyntaxKind::lexer(&new_text[new_range.start..new_range.end + tail_len]);
+    let mut new_tail = Vec::new();
+
+    while let Some(kind) = lexer.next() {
+        let span = lexer.span();
+        if span.start < boundary && span.end > boundary {
+            return false;
+        }
+
+        if span.start >= boundary {
+            new_tail.push((kind, span.len()));
+        }
+    }
+
+    old_tail == new_tail
+}
+
+// Lexes the trivia and the first significant token of the text.
+fn lex_tail(text: &str) -> Vec<(SyntaxKind, usize)> {
+    let mut tail = Vec::new();
+    let mut lexer = SyntaxKind::lexer(text);
+
+    while let Some(kind) = lexer.next() {
+        tail.push((kind, lexer.span().len()));
+        if !kind.is_trivia() {
+            break;
+        }
+    }
+
+    tail
+}
+
+impl<'source> Parser<'source> {
+    // Parses the source as the contents of a single block, returning nothing if
+    // the result might differ from parsing the block as part of its file.
+    fn run_block<I>(
+        mut self,
+        stop: &'static [SyntaxKind],
+        cache: &mut NodeCache<'_, I>,
+    ) -> Option<GreenNode>
+    where
+        I: Interner,
+    {
+        let marker = self.start();
+
+        // Without enclosing scopes, gotos leaving the block are reported as
+        // undefined, which makes this fall back to a full parse that can
+        // resolve them.
+        self.enter_label_scope(false);
+        self.r_block(stop);
+        self.exit_label_scope();
+        let complete = self.at() == T![eof];
+        marker.complete(&mut self, T![root]);
+
+        let (root, reports) = self.state.finish(cache);
+        (complete && reports.is_empty()).then(|| root)
+    }
+}
@@ -1,12 +1,48 @@
-use super::Parser;
+use super::{
+    diagnostic::{Code, Diagnostic},
+    Parser,
+};
 use crate::T;
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_items(&mut self) {
         while self.at() != T![eof] {
-            if self.r_stmt().is_none() {
-                break;
-            }
+            self.r_stmt();
         }
     }
+
+    pub(super) fn r_single_stmt(&mut self) {
+        if self.at() != T![eof] {
+            self.r_stmt();
+            return;
+        }
+
+        let error = Diagnostic::error(
+            Code::ExpectedStatement,
+            "expected a statement",
+            self.new_label("expected a statement but found the end of the input"),
+        );
+
+        self.syntax_error(error);
+    }
+
+    // Reports and skips everything following the part of the source an entry
+    // point parses.
+    pub(super) fn r_end_of_input(&mut self) {
+        if self.at() == T![eof] {
+            return;
+        }
+
+        let error = Diagnostic::error(
+            Code::ExpectedEndOfInput,
+            "expected the end of the input",
+            self.new_label(format!(
+                "expected the end of the input but found {}",
+                self.at()
+            )),
+        );
+
+        self.syntax_error(error);
+        self.error_eat_until(&[]);
+    }
 }
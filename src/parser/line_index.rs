@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+use fxhash::FxBuildHasher;
+use hashbrown::HashMap;
+
+/// A zero-based line and column. Depending on the conversion used, the column
+/// counts either UTF-8 bytes or UTF-16 code units from the start of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: u32,
+    pub col: u32,
+}
+
+impl LineCol {
+    pub fn new(line: u32, col: u32) -> Self {
+        Self { line, col }
+    }
+}
+
+// A character that takes up more than one byte, positioned by its UTF-8 column.
+#[derive(Debug, Clone, Copy)]
+struct WideChar {
+    start: u32,
+    end: u32,
+}
+
+impl WideChar {
+    fn len(self) -> u32 {
+        self.end - self.start
+    }
+
+    fn len_utf16(self) -> u32 {
+        if self.len() == 4 {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+/// Converts between byte offsets into a source and line/column positions.
+///
+/// Lines are terminated by `\n`, `\r\n` or a lone `\r`. The terminator is part
+/// of the line it ends.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<u32>,
+    wide_chars: HashMap<u32, Vec<WideChar>, FxBuildHasher>,
+    len: u32,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut wide_chars: HashMap<u32, Vec<WideChar>, FxBuildHasher> = HashMap::default();
+        let mut line_start = 0;
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((offset, c)) = chars.next() {
+            let offset = offset as u32;
+
+            match c {
+                '\r' if matches!(chars.peek(), Some((_, '\n'))) => {},
+                '\n' | '\r' => {
+                    line_start = offset + 1;
+                    line_starts.push(line_start);
+                },
+                c if !c.is_ascii() => {
+                    let line = line_starts.len() as u32 - 1;
+                    let start = offset - line_start;
+                    wide_chars.entry(line).or_default().push(WideChar {
+                        start,
+                        end: start + c.len_utf8() as u32,
+                    });
+                },
+                _ => {},
+            }
+        }
+
+        Self {
+            line_starts,
+            wide_chars,
+            len: text.len() as u32,
+        }
+    }
+
+    pub fn line_count(&self) -> u32 {
+        self.line_starts.len() as u32
+    }
+
+    /// The position of a byte offset, with the column in UTF-8 bytes.
+    pub fn line_col(&self, offset: u32) -> LineCol {
+        debug_assert!(offset <= self.len, "offset {} out of bounds", offset);
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        LineCol::new(line as u32, offset - self.line_starts[line])
+    }
+
+    /// The byte offset of a position with the column in UTF-8 bytes, if the
+    /// line exists and the offset lies within the source.
+    pub fn offset(&self, position: LineCol) -> Option<u32> {
+        let start = *self.line_starts.get(position.line as usize)?;
+        let end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .copied()
+            .unwrap_or(self.len);
+
+        let offset = start + position.col;
+        (offset <= end).then(|| offset)
+    }
+
+    /// Converts a column in UTF-8 bytes to one in UTF-16 code units.
+    pub fn to_utf16(&self, position: LineCol) -> LineCol {
+        let mut col = position.col;
+        for c in self.line_wide_chars(position.line) {
+            if c.end > position.col {
+                break;
+            }
+
+            col -= c.len() - c.len_utf16();
+        }
+
+        LineCol::new(position.line, col)
+    }
+
+    /// Converts a column in UTF-16 code units to one in UTF-8 bytes.
+    pub fn to_utf8(&self, position: LineCol) -> LineCol {
+        let mut col = position.col;
+        for c in self.line_wide_chars(position.line) {
+            if c.start >= col {
+                break;
+            }
+
+            col += c.len() - c.len_utf16();
+        }
+
+        LineCol::new(position.line, col)
+    }
+
+    /// The position of a byte offset, with the column in UTF-16 code units.
+    pub fn line_col_utf16(&self, offset: u32) -> LineCol {
+        self.to_utf16(self.line_col(offset))
+    }
+
+    /// The byte offset of a position with the column in UTF-16 code units.
+    pub fn offset_utf16(&self, position: LineCol) -> Option<u32> {
+        self.offset(self.to_utf8(position))
+    }
+
+    /// Converts a byte range, such as a [`Span`](super::machinery::span::Span)
+    /// or the text range of a syntax node, into a range of positions with
+    /// columns in UTF-8 bytes.
+    pub fn range(&self, range: impl Into<Range<u32>>) -> Range<LineCol> {
+        let range = range.into();
+        self.line_col(range.start)..self.line_col(range.end)
+    }
+
+    /// Like [`LineIndex::range`] but with columns in UTF-16 code units.
+    pub fn range_utf16(&self, range: impl Into<Range<u32>>) -> Range<LineCol> {
+        let range = range.into();
+        self.line_col_utf16(range.start)..self.line_col_utf16(range.end)
+    }
+
+    fn line_wide_chars(&self, line: u32) -> &[WideChar] {
+        self.wide_chars
+            .get(&line)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,461 @@
+use std::ops::Range;
+
+/// A problem found while decoding a literal. The range is relative to the
+/// start of the literal's text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralError {
+    pub range: Range<usize>,
+    pub message: &'static str,
+}
+
+/// Decodes the text of a short or long string literal, including its
+/// delimiters, into the bytes it evaluates to.
+///
+/// Decoding continues past invalid escapes so that all of them are reported.
+pub fn decode_string(text: &str) -> Result<Vec<u8>, Vec<LiteralError>> {
+    let decoder = StringDecoder::new(text, Some(Vec::with_capacity(text.len())));
+    let (value, errors) = decoder.run();
+    if errors.is_empty() {
+        Ok(value.unwrap_or_default())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks the text of a string literal for the errors [`decode_string`] would
+/// report, without building its value.
+pub fn validate_string(text: &str) -> Result<(), Vec<LiteralError>> {
+    let (_, errors) = StringDecoder::new(text, None).run();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The value of a numeric literal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+/// Converts the text of a numeric literal into its value the way Lua does.
+/// Decimal integers that don't fit into 64 bits become floats, hexadecimal
+/// ones wrap around. LuaJIT's `LL` and `ULL` suffixes are accepted and always
+/// produce wrapped integers.
+///
+/// Returns nothing if the text isn't a valid numeral.
+pub fn decode_number(text: &str) -> Option<Number> {
+    let (hex, digits) = match text.get(..2) {
+        Some("0x" | "0X") => (true, &text[2..]),
+        _ => (false, text),
+    };
+
+    let (digits, suffixed) = match strip_int_suffix(digits) {
+        Some(digits) => (digits, true),
+        None => (digits, false),
+    };
+
+    let radix = if hex { 16 } else { 10 };
+    if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) {
+        return Some(decode_int(digits, radix, hex || suffixed));
+    }
+
+    if suffixed {
+        None
+    } else if hex {
+        decode_hex_float(digits)
+    } else {
+        decode_float(digits)
+    }
+}
+
+fn strip_int_suffix(text: &str) -> Option<&str> {
+    let text = text
+        .strip_suffix(&['l', 'L'][..])?
+        .strip_suffix(&['l', 'L'][..])?;
+
+    Some(text.strip_suffix(&['u', 'U'][..]).unwrap_or(text))
+}
+
+fn decode_int(digits: &str, radix: u32, wrap: bool) -> Number {
+    let mut value: u64 = 0;
+    for digit in digits.chars().filter_map(|c| c.to_digit(radix)) {
+        let digit = u64::from(digit);
+        if wrap {
+            value = value.wrapping_mul(u64::from(radix)).wrapping_add(digit);
+            continue;
+        }
+
+        match value
+            .checked_mul(10)
+            .and_then(|value| value.checked_add(digit))
+            .filter(|&value| value <= i64::MAX as u64)
+        {
+            Some(next) => value = next,
+            None => return Number::Float(digits.parse().unwrap_or(f64::INFINITY)),
+        }
+    }
+
+    Number::Int(value as i64)
+}
+
+fn decode_float(text: &str) -> Option<Number> {
+    // Rust also accepts signs and names like `inf`, Lua numerals don't.
+    let valid = text.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'));
+
+    if !valid {
+        return None;
+    }
+
+    text.parse().ok().map(Number::Float)
+}
+
+// Follows `lua_strx2number`, which reads at most 30 significant digits and
+// only counts the rest towards the exponent.
+fn decode_hex_float(text: &str) -> Option<Number> {
+    const MAX_SIGNIFICANT: u32 = 30;
+
+    let (mantissa, exponent) = match text.find(&['p', 'P'][..]) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+
+    let mut value = 0.0;
+    let mut exp: i32 = 0;
+    let mut significant = 0;
+    let mut any = false;
+    let mut dot = false;
+
+    for c in mantissa.chars() {
+        if c == '.' && !dot {
+            dot = true;
+            continue;
+        }
+
+        let digit = c.to_digit(16)?;
+        any = true;
+
+        // Leading zeros aren't significant.
+        if significant > 0 || digit != 0 {
+            if significant < MAX_SIGNIFICANT {
+                significant += 1;
+                value = value * 16.0 + f64::from(digit);
+            } else {
+                exp += 1;
+            }
+        }
+
+        if dot {
+            exp -= 1;
+        }
+    }
+
+    if !any {
+        return None;
+    }
+
+    exp *= 4;
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(&['+', '-'][..]).unwrap_or(exponent);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        // Exponents that don't fit make the value infinite or zero anyway.
+        let limit = if exponent.starts_with('-') {
+            i32::MIN
+        } else {
+            i32::MAX
+        };
+
+        exp = exp.saturating_add(exponent.parse().unwrap_or(limit));
+    }
+
+    Some(Number::Float(ldexp(value, exp)))
+}
+
+// Computes `value * 2^exp`, scaling in two steps so that large exponents don't
+// overflow before the mantissa is applied.
+fn ldexp(value: f64, exp: i32) -> f64 {
+    if value == 0.0 {
+        return value;
+    }
+
+    let half = exp / 2;
+    value * 2f64.powi(half) * 2f64.powi(exp - half)
+}
+
+pub(crate) fn is_lua_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c')
+}
+
+// Only collects the value if it's given a buffer to decode into.
+struct StringDecoder<'a> {
+    bytes: &'a [u8],
+    value: Option<Vec<u8>>,
+    errors: Vec<LiteralError>,
+}
+
+impl<'a> StringDecoder<'a> {
+    fn new(text: &'a str, value: Option<Vec<u8>>) -> Self {
+        Self {
+            bytes: text.as_bytes(),
+            value,
+            errors: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> (Option<Vec<u8>>, Vec<LiteralError>) {
+        if self.bytes.first() == Some(&b'[') {
+            self.decode_long();
+        } else {
+            self.decode_short();
+        }
+
+        (self.value, self.errors)
+    }
+
+    fn push(&mut self, c: u8) {
+        if let Some(value) = &mut self.value {
+            value.push(c);
+        }
+    }
+
+    fn push_utf8(&mut self, c: u32) {
+        if let Some(value) = &mut self.value {
+            encode_utf8(c, value);
+        }
+    }
+
+    fn error(&mut self, range: Range<usize>, message: &'static str) {
+        self.errors.push(LiteralError { range, message });
+    }
+
+    fn decode_long(&mut self) {
+        let level = self.bytes[1..].iter().take_while(|&&c| c == b'=').count();
+        let open = level + 2;
+        let mut delimiter = vec![b'='; open];
+        delimiter[0] = b']';
+        delimiter[level + 1] = b']';
+
+        let opened = self.bytes.get(level + 1) == Some(&b'[');
+        if !opened || self.bytes.len() < 2 * open || !self.bytes.ends_with(&delimiter) {
+            self.error(0..self.bytes.len(), "unterminated long string");
+            return;
+        }
+
+        let close = self.bytes.len() - open;
+
+        // A newline directly after the opening bracket isn't part of the string.
+        let mut i = self.skip_newline(open);
+        while i < close {
+            match self.bytes[i] {
+                b'\n' | b'\r' => {
+                    self.push(b'\n');
+                    i = self.skip_newline(i);
+                },
+                c => {
+                    self.push(c);
+                    i += 1;
+                },
+            }
+        }
+    }
+
+    fn decode_short(&mut self) {
+        let quote = self.bytes[0];
+        let mut i = 1;
+
+        loop {
+            match self.bytes.get(i) {
+                None | Some(b'\n' | b'\r') => {
+                    self.error(0..i, "unterminated string");
+                    return;
+                },
+                Some(&c) if c == quote => return,
+                Some(b'\\') => i = self.decode_escape(i),
+                Some(&c) => {
+                    self.push(c);
+                    i += 1;
+                },
+            }
+        }
+    }
+
+    // Decodes the escape sequence starting at the backslash at `start` and
+    // returns the position following it.
+    fn decode_escape(&mut self, start: usize) -> usize {
+        let i = start + 1;
+        let simple = match self.bytes.get(i) {
+            Some(b'a') => Some(b'\x07'),
+            Some(b'b') => Some(b'\x08'),
+            Some(b'f') => Some(b'\x0c'),
+            Some(b'n') => Some(b'\n'),
+            Some(b'r') => Some(b'\r'),
+            Some(b't') => Some(b'\t'),
+            Some(b'v') => Some(b'\x0b'),
+            Some(&c @ (b'\\' | b'"' | b'\'')) => Some(c),
+            _ => None,
+        };
+
+        if let Some(c) = simple {
+            self.push(c);
+            return i + 1;
+        }
+
+        match self.bytes.get(i) {
+            // Leave the error to the caller.
+            None => i,
+            Some(b'\n' | b'\r') => {
+                self.push(b'\n');
+                self.skip_newline(i)
+            },
+            Some(b'z') => {
+                let mut i = i + 1;
+                while self.bytes.get(i).copied().map_or(false, is_lua_whitespace) {
+                    i += 1;
+                }
+
+                i
+            },
+            Some(b'x') => self.decode_hex_escape(start),
+            Some(b'u') => self.decode_utf8_escape(start),
+            Some(c) if c.is_ascii_digit() => self.decode_decimal_escape(start),
+            Some(&c) => {
+                // Skip the whole character following the backslash.
+                let len = match c {
+                    0xf0..=0xff => 4,
+                    0xe0..=0xef => 3,
+                    0xc0..=0xdf => 2,
+                    _ => 1,
+                };
+
+                let end = (i + len).min(self.bytes.len());
+                self.error(start..end, "invalid escape sequence");
+                end
+            },
+        }
+    }
+
+    fn decode_hex_escape(&mut self, start: usize) -> usize {
+        let digits = self.hex_digits(start + 2, 2);
+        if digits.len() < 2 {
+            self.error(start..digits.end, "hexadecimal digit expected");
+            return digits.end;
+        }
+
+        let end = digits.end;
+        let value = self.hex_value(digits).unwrap_or_default();
+        self.push(value as u8);
+        end
+    }
+
+    fn decode_decimal_escape(&mut self, start: usize) -> usize {
+        let first = start + 1;
+        let end = first
+            + self.bytes[first..]
+                .iter()
+                .take(3)
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+
+        let value = self.bytes[first..end]
+            .iter()
+            .fold(0, |value, c| value * 10 + u32::from(c - b'0'));
+
+        if value > 255 {
+            self.error(start..end, "decimal escape too large");
+        } else {
+            self.push(value as u8);
+        }
+
+        end
+    }
+
+    fn decode_utf8_escape(&mut self, start: usize) -> usize {
+        let open = start + 2;
+        if self.bytes.get(open) != Some(&b'{') {
+            self.error(start..open, "missing '{' in \\u{xxxx}");
+            return open;
+        }
+
+        let digits = self.hex_digits(open + 1, usize::MAX);
+        if digits.is_empty() {
+            self.error(start..digits.end, "hexadecimal digit expected");
+            return digits.end;
+        }
+
+        if self.bytes.get(digits.end) != Some(&b'}') {
+            self.error(start..digits.end, "missing '}' in \\u{xxxx}");
+            return digits.end;
+        }
+
+        let end = digits.end + 1;
+        match self.hex_value(digits) {
+            Some(value) if value <= 0x7fff_ffff => self.push_utf8(value),
+            _ => self.error(start..end, "UTF-8 value too large"),
+        }
+
+        end
+    }
+
+    fn hex_digits(&self, start: usize, max: usize) -> Range<usize> {
+        let count = self.bytes[start.min(self.bytes.len())..]
+            .iter()
+            .take(max)
+            .take_while(|c| c.is_ascii_hexdigit())
+            .count();
+
+        start..start + count
+    }
+
+    fn hex_value(&self, digits: Range<usize>) -> Option<u32> {
+        self.bytes[digits].iter().try_fold(0u32, |value, &c| {
+            let digit = (c as char).to_digit(16)?;
+            value.checked_mul(16)?.checked_add(digit)
+        })
+    }
+
+    // Skips a newline sequence, where `\r\n` and `\n\r` count as one.
+    fn skip_newline(&self, i: usize) -> usize {
+        match self.bytes.get(i..i + 2) {
+            Some(b"\r\n" | b"\n\r") => i + 2,
+            _ => match self.bytes.get(i) {
+                Some(b'\n' | b'\r') => i + 1,
+                _ => i,
+            },
+        }
+    }
+}
+
+// Encodes a code point the way Lua does, which allows values up to 2^31 by
+// using sequences of up to six bytes.
+fn encode_utf8(mut value: u32, out: &mut Vec<u8>) {
+    if value < 0x80 {
+        out.push(value as u8);
+        return;
+    }
+
+    let mut buffer = [0; 6];
+    let mut len = 0;
+    let mut max_first = 0x3f;
+
+    loop {
+        buffer[5 - len] = 0x80 | (value & 0x3f) as u8;
+        len += 1;
+        value >>= 6;
+        max_first >>= 1;
+
+        if value <= max_first {
+            break;
+        }
+    }
+
+    buffer[5 - len] = ((!max_first << 1) | value) as u8;
+    out.extend_from_slice(&buffer[5 - len..]);
+}
@@ -26,10 +26,48 @@ pub fn token_is_expr_start(token: SyntaxKind) -> bool {
         || token == T![...]
 }
 
+// Only names, parenthesised expressions and other prefix expressions can be
+// called, indexed or have their fields accessed.
+pub fn node_is_prefix_expr(node: SyntaxKind) -> bool {
+    matches!(
+        node,
+        T![ident] | T![expr] | T![field_expr] | T![index] | T![func_call] | T![method_call_expr]
+    )
+}
+
+pub fn token_is_stmt_start(token: SyntaxKind) -> bool {
+    matches!(
+        token,
+        T![do]
+            | T![while]
+            | T![repeat]
+            | T![if]
+            | T![for]
+            | T![return]
+            | T![break]
+            | T![goto]
+            | T![::]
+            | T![function]
+            | T![local]
+            | T![ident]
+            | T!['(']
+            | T![;]
+            | T![invalid]
+    )
+}
+
+pub fn token_is_call_args_start(token: SyntaxKind) -> bool {
+    matches!(token, T!['('] | T![string] | T![long_string] | T!['{'])
+}
+
 pub fn token_is_unary_op(token: SyntaxKind) -> bool {
     matches!(token, T![not] | T![+] | T![-] | T![#] | T![~])
 }
 
+pub fn token_is_bitwise_op(token: SyntaxKind) -> bool {
+    matches!(token, T![&] | T![|] | T![~] | T![<<] | T![>>])
+}
+
 pub fn token_is_binary_op(token: SyntaxKind) -> bool {
     matches!(
         token,
@@ -53,8 +91,6 @@ pub fn token_is_binary_op(token: SyntaxKind) -> bool {
             | T![>=]
             | T![>]
             | T![<]
-            | T![.]
-            | T![:]
             | T![..]
     )
 }
@@ -12,6 +12,10 @@ pub enum Event {
         kind: SyntaxKind,
         span: Span,
     },
+    // A token the parser expected but didn't find, which takes up no text.
+    Missing {
+        kind: SyntaxKind,
+    },
 }
 
 impl Event {
@@ -1,9 +1,15 @@
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    ops::Range,
+};
 
 use logos::{Lexer, Logos};
 
+use crate::parser::literal::is_lua_whitespace;
+
 #[allow(clippy::manual_non_exhaustive)]
 #[derive(Logos, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[logos(extras = Vec<LexError>)]
 #[repr(u16)]
 pub enum SyntaxKind {
     // Miscellaneous
@@ -26,7 +32,6 @@ pub enum SyntaxKind {
     ForGenStmt,
     FuncStmt,
     FuncArgs,
-    SimpleExpr,
     Expr,
     VarArgExpr,
     BinOp,
@@ -43,13 +48,26 @@ pub enum SyntaxKind {
     TableGenericElem,
     AssignStmt,
     LiteralExpr,
-
-    #[regex(r"[ \n\t\f\r]+", logos::skip)]
+    GotoStmt,
+    LabelStmt,
+    FieldExpr,
+    MethodCallExpr,
+    MethodName,
+    StringArg,
+    TableArg,
+    Missing,
+    Attrib,
+
+    #[regex(r"[ \n\t\f\r]+")]
     Whitespace,
 
-    #[regex("--", skip_comment)]
+    #[regex("--", comment)]
     Comment,
 
+    // Only recognized at the start of a file, see `lex_header`.
+    Bom,
+    Shebang,
+
     // Operators
     #[token("+")]
     Plus,
@@ -163,6 +181,9 @@ pub enum SyntaxKind {
     #[token("return")]
     Return,
 
+    #[token("goto")]
+    Goto,
+
     #[token("not")]
     Not,
 
@@ -172,12 +193,6 @@ pub enum SyntaxKind {
     #[token("and")]
     And,
 
-    #[token("<const>")]
-    Const,
-
-    #[token("<close>")]
-    Close,
-
     // Literals
     #[token("nil")]
     Nil,
@@ -188,23 +203,29 @@ pub enum SyntaxKind {
     #[token("false")]
     False,
 
-    #[regex(r#""(\\[\\"]|[^"])*""#)]
-    #[regex(r#"'(\\[\\']|[^'])*'"#)]
+    #[regex(r#"["']"#, string)]
     String,
 
     #[regex(r"\[=*\[", long_string)]
     LongString,
 
-    #[regex(r"[0-9]+", priority = 2)]
+    // Numerals touching further digits, letters or dots are rejected as a
+    // whole by the callback, like `3..2` or `0xg`.
+    #[regex(r"[0-9]+([uU]?[lL][lL])?", numeral)]
     Int,
 
-    #[regex(r"0x[0-9a-fA-F]+")]
+    #[regex(r"0[xX][0-9a-fA-F]+([uU]?[lL][lL])?", numeral)]
     HexInt,
 
-    #[regex(r"[0-9]+(\.[0-9]+)?([eE][+-]?[0-9]+)?")]
+    #[regex(r"([0-9]+\.[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?", numeral)]
+    #[regex(r"[0-9]+[eE][+-]?[0-9]+", numeral)]
     Float,
 
-    #[regex(r"0x[0-9a-fA-F]*\.[0-9a-fA-F]+([pP][+-][0-9a-fA-F]+)?")]
+    #[regex(
+        r"0[xX]([0-9a-fA-F]+\.[0-9a-fA-F]*|\.[0-9a-fA-F]+)([pP][+-]?[0-9]+)?",
+        numeral
+    )]
+    #[regex(r"0[xX][0-9a-fA-F]+[pP][+-]?[0-9]+", numeral)]
     HexFloat,
 
     // Grouping
@@ -251,82 +272,159 @@ pub enum SyntaxKind {
 
 impl SyntaxKind {
     pub fn is_trivia(self) -> bool {
-        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+        matches!(
+            self,
+            SyntaxKind::Whitespace | SyntaxKind::Comment | SyntaxKind::Bom | SyntaxKind::Shebang
+        )
     }
 }
 
-fn long_string(lexer: &mut Lexer<SyntaxKind>) {
-    let delim_len = lexer.slice().len();
-    let rem = lexer.remainder();
+/// Lexes a UTF-8 byte order mark and a shebang line at the very start of a
+/// file. Both are kept as trivia so that the tree still covers the whole file,
+/// the rest of it is lexed as usual.
+pub fn lex_header(source: &str) -> Vec<(SyntaxKind, Range<usize>)> {
+    let mut header = Vec::new();
+    let mut start = 0;
 
-    for (i, _) in rem.char_indices() {
-        if is_long_delimiter(&rem[i..i + delim_len], ']') {
-            lexer.bump(i + delim_len);
-            return;
-        }
+    if source.starts_with('\u{feff}') {
+        start = '\u{feff}'.len_utf8();
+        header.push((SyntaxKind::Bom, 0..start));
     }
 
-    unreachable!()
-}
+    // The line break after the shebang is ordinary whitespace.
+    if source[start..].starts_with("#!") {
+        let end = source[start..]
+            .find(&['\n', '\r'][..])
+            .map_or(source.len(), |len| start + len);
 
-fn skip_comment(lexer: &mut Lexer<SyntaxKind>) -> logos::Skip {
-    let rem = lexer.remainder();
-
-    if let Some(delim_len) = starts_with_long_delimiter(rem, '[') {
-        lexer.bump(delim_len);
-        skip_long_comment(lexer, delim_len);
-        logos::Skip
-    } else {
-        for (i, _) in rem.char_indices() {
-            let curr = &rem[i..];
-            if curr.starts_with("\r\n") {
-                lexer.bump(i - 1);
-                return logos::Skip;
-            }
-
-            if curr.starts_with('\n') {
-                lexer.bump(i);
-                return logos::Skip;
-            }
-        }
-
-        unreachable!();
+        header.push((SyntaxKind::Shebang, start..end));
     }
+
+    header
 }
 
-fn skip_long_comment(lexer: &mut Lexer<SyntaxKind>, delim_len: usize) {
-    let rem = lexer.remainder();
+/// An error encountered while lexing, attached to the token it invalidated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub range: Range<usize>,
+    pub message: &'static str,
+}
 
-    for (i, _) in rem.char_indices() {
-        if is_long_delimiter(&rem[i..i + delim_len], ']') {
-            lexer.bump(i + delim_len);
-            return;
+// Finds the end of a short string. Escapes are only skipped here, they are
+// validated when the string is decoded.
+fn string(lexer: &mut Lexer<SyntaxKind>) -> bool {
+    let quote = lexer.slice().as_bytes()[0];
+    let rem = lexer.remainder().as_bytes();
+    let mut i = 0;
+
+    while let Some(&c) = rem.get(i) {
+        match c {
+            b'\\' => match &rem[i + 1..] {
+                [b'\r', b'\n', ..] | [b'\n', b'\r', ..] => i += 3,
+                [b'z', ..] => {
+                    i += 2;
+                    while rem.get(i).copied().map_or(false, is_lua_whitespace) {
+                        i += 1;
+                    }
+                },
+                _ => i += 2,
+            },
+            b'\n' | b'\r' => break,
+            c if c == quote => {
+                lexer.bump(i + 1);
+                return true;
+            },
+            _ => i += 1,
         }
     }
 
-    unreachable!()
+    lexer.bump(i.min(rem.len()));
+    let range = lexer.span();
+    lexer.extras.push(LexError {
+        range,
+        message: "unterminated string",
+    });
+
+    false
 }
 
-fn starts_with_long_delimiter(slice: &str, delim: char) -> Option<usize> {
-    if !slice.starts_with("[[") && !slice.starts_with("[=]") {
-        return None;
+// Extends a numeral over everything Lua would read as part of it, and fails if
+// that is more than the regex matched.
+fn numeral(lexer: &mut Lexer<SyntaxKind>) -> bool {
+    let slice = lexer.slice().as_bytes();
+    let hex = slice.len() > 1 && matches!(slice[1], b'x' | b'X');
+    let exponent: &[u8] = if hex { b"pP" } else { b"eE" };
+
+    let rem = lexer.remainder().as_bytes();
+    let mut previous = slice[slice.len() - 1];
+    let mut i = 0;
+
+    while let Some(&c) = rem.get(i) {
+        let sign = matches!(c, b'+' | b'-') && exponent.contains(&previous);
+        if !(c.is_ascii_alphanumeric() || c == b'_' || c == b'.' || sign) {
+            break;
+        }
+
+        previous = c;
+        i += 1;
     }
 
-    for (i, _) in slice.char_indices() {
-        if is_long_delimiter(&slice[..i], delim) {
-            return Some(i);
-        }
+    if i == 0 {
+        return true;
     }
 
-    None
+    lexer.bump(i);
+    let range = lexer.span();
+    lexer.extras.push(LexError {
+        range,
+        message: "malformed number",
+    });
+
+    false
 }
 
-fn is_long_delimiter(slice: &str, delim: char) -> bool {
-    if slice.len() < 2 || !slice.starts_with(delim) || !slice.ends_with(delim) {
-        return false;
+fn long_string(lexer: &mut Lexer<SyntaxKind>) -> bool {
+    let level = lexer.slice().len() - 2;
+    long_bracket_close(lexer, level, "unterminated long string")
+}
+
+fn comment(lexer: &mut Lexer<SyntaxKind>) -> bool {
+    let rem = lexer.remainder();
+
+    if let Some(level) = long_bracket_level(rem) {
+        lexer.bump(level + 2);
+        long_bracket_close(lexer, level, "unterminated long comment")
+    } else {
+        let len = rem.find(|c| c == '\n' || c == '\r').unwrap_or(rem.len());
+        lexer.bump(len);
+        true
     }
+}
+
+fn long_bracket_close(lexer: &mut Lexer<SyntaxKind>, level: usize, error: &'static str) -> bool {
+    let delim = format!("]{}]", "=".repeat(level));
+    let rem = lexer.remainder();
+
+    if let Some(i) = rem.find(&delim) {
+        lexer.bump(i + delim.len());
+        true
+    } else {
+        lexer.bump(rem.len());
+        let range = lexer.span();
+        lexer.extras.push(LexError {
+            range,
+            message: error,
+        });
+
+        false
+    }
+}
 
-    slice.chars().filter(|c| *c == '=').count() + 2 == slice.len()
+// Returns the level of the long bracket at the start of the slice, if any.
+fn long_bracket_level(slice: &str) -> Option<usize> {
+    let rem = slice.strip_prefix('[')?;
+    let level = rem.bytes().take_while(|b| *b == b'=').count();
+    rem[level..].starts_with('[').then(|| level)
 }
 
 #[macro_export]
@@ -347,7 +445,6 @@ macro_rules! T {
     [for_gen_stmt] => { $crate::parser::machinery::kind::SyntaxKind::ForGenStmt };
     [func_stmt] => { $crate::parser::machinery::kind::SyntaxKind::FuncStmt };
     [func_args] => { $crate::parser::machinery::kind::SyntaxKind::FuncArgs };
-    [simple_expr] => { $crate::parser::machinery::kind::SyntaxKind::SimpleExpr };
     [expr] => { $crate::parser::machinery::kind::SyntaxKind::Expr };
     [vararg_expr] => { $crate::parser::machinery::kind::SyntaxKind::VarArgExpr };
     [bin_op] => { $crate::parser::machinery::kind::SyntaxKind::BinOp };
@@ -364,6 +461,19 @@ macro_rules! T {
     [table_generic_elem] => { $crate::parser::machinery::kind::SyntaxKind::TableGenericElem };
     [assign_stmt] => { $crate::parser::machinery::kind::SyntaxKind::AssignStmt };
     [literal_expr] => { $crate::parser::machinery::kind::SyntaxKind::LiteralExpr };
+    [goto_stmt] => { $crate::parser::machinery::kind::SyntaxKind::GotoStmt };
+    [label_stmt] => { $crate::parser::machinery::kind::SyntaxKind::LabelStmt };
+    [field_expr] => { $crate::parser::machinery::kind::SyntaxKind::FieldExpr };
+    [method_call_expr] => { $crate::parser::machinery::kind::SyntaxKind::MethodCallExpr };
+    [method_name] => { $crate::parser::machinery::kind::SyntaxKind::MethodName };
+    [string_arg] => { $crate::parser::machinery::kind::SyntaxKind::StringArg };
+    [table_arg] => { $crate::parser::machinery::kind::SyntaxKind::TableArg };
+    [missing] => { $crate::parser::machinery::kind::SyntaxKind::Missing };
+    [attrib] => { $crate::parser::machinery::kind::SyntaxKind::Attrib };
+    [whitespace] => { $crate::parser::machinery::kind::SyntaxKind::Whitespace };
+    [comment] => { $crate::parser::machinery::kind::SyntaxKind::Comment };
+    [bom] => { $crate::parser::machinery::kind::SyntaxKind::Bom };
+    [shebang] => { $crate::parser::machinery::kind::SyntaxKind::Shebang };
     [ident] => { $crate::parser::machinery::kind::SyntaxKind::Ident };
     [+] => { $crate::parser::machinery::kind::SyntaxKind::Plus };
     [-] => { $crate::parser::machinery::kind::SyntaxKind::Minus };
@@ -400,11 +510,10 @@ macro_rules! T {
     [if] => { $crate::parser::machinery::kind::SyntaxKind::If };
     [repeat] => { $crate::parser::machinery::kind::SyntaxKind::Repeat };
     [return] => { $crate::parser::machinery::kind::SyntaxKind::Return };
+    [goto] => { $crate::parser::machinery::kind::SyntaxKind::Goto };
     [not] => { $crate::parser::machinery::kind::SyntaxKind::Not };
     [or] => { $crate::parser::machinery::kind::SyntaxKind::Or };
     [and] => { $crate::parser::machinery::kind::SyntaxKind::And };
-    [const] => { $crate::parser::machinery::kind::SyntaxKind::Const };
-    [close] => { $crate::parser::machinery::kind::SyntaxKind::Close };
     [nil] => { $crate::parser::machinery::kind::SyntaxKind::Nil };
     [true] => { $crate::parser::machinery::kind::SyntaxKind::True };
     [false] => { $crate::parser::machinery::kind::SyntaxKind::False };
@@ -438,6 +547,10 @@ impl Display for SyntaxKind {
             match self {
                 T![invalid] => "INVALID",
                 T![eof] => "EOF",
+                T![whitespace] => "WHITESPACE",
+                T![comment] => "COMMENT",
+                T![bom] => "BOM",
+                T![shebang] => "SHEBANG",
                 T![root] => "ROOT",
                 T![ident] => "IDENTIFIER",
                 T![+] => "PLUS",
@@ -475,11 +588,10 @@ impl Display for SyntaxKind {
                 T![if] => "IF",
                 T![repeat] => "REPEAT",
                 T![return] => "RETURN",
+                T![goto] => "GOTO",
                 T![not] => "NOT",
                 T![or] => "OR",
                 T![and] => "AND",
-                T![const] => "CONST",
-                T![close] => "CLOSE",
                 T![nil] => "NIL",
                 T![true] => "TRUE",
                 T![false] => "FALSE",
@@ -489,19 +601,58 @@ impl Display for SyntaxKind {
                 T![hex_int] => "HEX_INT",
                 T![float] => "FLOAT",
                 T![hex_float] => "HEX_FLOAT",
-                T!['('] => "RPAREN",
-                T![')'] => "LPAREN",
-                T!['{'] => "RCURLY",
-                T!['}'] => "LCURLY",
-                T!['['] => "RBRACKET",
-                T![']'] => "LBRACKET",
+                T!['('] => "LPAREN",
+                T![')'] => "RPAREN",
+                T!['{'] => "LCURLY",
+                T!['}'] => "RCURLY",
+                T!['['] => "LBRACKET",
+                T![']'] => "RBRACKET",
                 T![:] => "COLON",
                 T![::] => "DCOLON",
                 T![,] => "COMMA",
                 T![.] => "DOT",
                 T![..] => "DDOT",
                 T![...] => "TDOT",
-                _ => unreachable!(),
+                T![;] => "SEMICOLON",
+                T![tombstone] => "TOMBSTONE",
+                T![break_stmt] => "BREAK_STMT",
+                T![return_stmt] => "RETURN_STMT",
+                T![block_stmt] => "BLOCK_STMT",
+                T![while_stmt] => "WHILE_STMT",
+                T![repeat_stmt] => "REPEAT_STMT",
+                T![stmt_list] => "STMT_LIST",
+                T![if_stmt] => "IF_STMT",
+                T![else_chain] => "ELSE_CHAIN",
+                T![for_num_stmt] => "FOR_NUM_STMT",
+                T![for_gen_stmt] => "FOR_GEN_STMT",
+                T![func_stmt] => "FUNC_STMT",
+                T![func_args] => "FUNC_ARGS",
+                T![expr] => "EXPR",
+                T![vararg_expr] => "VARARG_EXPR",
+                T![bin_op] => "BIN_OP",
+                T![func_call] => "FUNC_CALL",
+                T![index] => "INDEX",
+                T![expr_list] => "EXPR_LIST",
+                T![decl_stmt] => "DECL_STMT",
+                T![decl_target] => "DECL_TARGET",
+                T![func_expr] => "FUNC_EXPR",
+                T![prefix_op] => "PREFIX_OP",
+                T![table_expr] => "TABLE_EXPR",
+                T![table_array_elem] => "TABLE_ARRAY_ELEM",
+                T![table_map_elem] => "TABLE_MAP_ELEM",
+                T![table_generic_elem] => "TABLE_GENERIC_ELEM",
+                T![assign_stmt] => "ASSIGN_STMT",
+                T![literal_expr] => "LITERAL_EXPR",
+                T![goto_stmt] => "GOTO_STMT",
+                T![label_stmt] => "LABEL_STMT",
+                T![field_expr] => "FIELD_EXPR",
+                T![method_call_expr] => "METHOD_CALL_EXPR",
+                T![method_name] => "METHOD_NAME",
+                T![string_arg] => "STRING_ARG",
+                T![table_arg] => "TABLE_ARG",
+                T![missing] => "MISSING",
+                T![attrib] => "ATTRIB",
+                T![__LAST] => "__LAST",
             }
         )
     }
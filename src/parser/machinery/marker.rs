@@ -52,6 +52,10 @@ pub struct CompletedMarker {
 }
 
 impl CompletedMarker {
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
     pub fn precede(self, state: &mut State) -> Marker {
         let marker = state.start();
 
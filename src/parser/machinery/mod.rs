@@ -3,6 +3,7 @@ pub mod classifiers;
 pub mod event;
 pub mod kind;
 pub mod marker;
+pub mod scope;
 pub mod sink;
 pub mod span;
 pub mod state;
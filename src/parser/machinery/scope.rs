@@ -0,0 +1,38 @@
+use super::span::Span;
+
+/// Tracks the labels declared and the gotos issued within a single block.
+pub struct LabelScope {
+    function: bool,
+    labels: Vec<Span>,
+    gotos: Vec<Span>,
+}
+
+impl LabelScope {
+    pub fn new(function: bool) -> Self {
+        Self {
+            function,
+            labels: Vec::new(),
+            gotos: Vec::new(),
+        }
+    }
+
+    pub fn is_function(&self) -> bool {
+        self.function
+    }
+
+    pub fn labels(&self) -> &[Span] {
+        &self.labels
+    }
+
+    pub fn add_label(&mut self, name: Span) {
+        self.labels.push(name);
+    }
+
+    pub fn add_goto(&mut self, target: Span) {
+        self.gotos.push(target);
+    }
+
+    pub fn take_gotos(&mut self) -> Vec<Span> {
+        std::mem::take(&mut self.gotos)
+    }
+}
@@ -1,21 +1,25 @@
 use std::mem;
 
-use cstree::{GreenNode, GreenNodeBuilder, NodeCache};
+use cstree::{interning::Interner, GreenNode, GreenNodeBuilder, NodeCache};
 
 use super::{event::Event, kind::SyntaxKind, span::Span};
 use crate::T;
 
-pub struct Sink<'cache, 'source> {
-    builder: GreenNodeBuilder<'cache, 'static>,
+pub struct Sink<'cache, 'interner, 'source, I> {
+    builder: GreenNodeBuilder<'cache, 'interner, I>,
     tokens: &'source [(SyntaxKind, Span)],
     cursor: usize,
+    depth: usize,
     events: Vec<Event>,
     source: &'source str,
 }
 
-impl<'cache, 'source> Sink<'cache, 'source> {
+impl<'cache, 'interner, 'source, I> Sink<'cache, 'interner, 'source, I>
+where
+    I: Interner,
+{
     pub fn new(
-        cache: &'cache mut NodeCache<'static>,
+        cache: &'cache mut NodeCache<'interner, I>,
         tokens: &'source [(SyntaxKind, Span)],
         events: Vec<Event>,
         source: &'source str,
@@ -24,6 +28,7 @@ impl<'cache, 'source> Sink<'cache, 'source> {
             builder: GreenNodeBuilder::with_cache(cache),
             tokens,
             cursor: 0,
+            depth: 0,
             events,
             source,
         }
@@ -34,6 +39,18 @@ impl<'cache, 'source> Sink<'cache, 'source> {
         self.builder.token(kind.into(), text);
     }
 
+    // Attaches all trivia preceding the next significant token to the node
+    // that is currently open.
+    fn eat_trivia(&mut self) {
+        while let Some(&(kind, span)) = self.tokens.get(self.cursor) {
+            if !kind.is_trivia() {
+                break;
+            }
+
+            self.token(kind, &self.source[span]);
+        }
+    }
+
     pub fn finish(mut self) -> GreenNode {
         let mut preceded_nodes = Vec::new();
         for idx in 0..self.events.len() {
@@ -61,18 +78,35 @@ impl<'cache, 'source> Sink<'cache, 'source> {
                         }
                     }
 
+                    // Trivia can't be placed outside of the root node.
+                    if self.depth > 0 {
+                        self.eat_trivia();
+                    }
+
                     for kind in preceded_nodes.drain(..).rev() {
+                        self.depth += 1;
                         self.builder.start_node(kind.into());
                     }
                 },
 
                 Event::Exit => {
+                    // Trailing trivia at the end of the file belongs to the root.
+                    if self.depth == 1 {
+                        self.eat_trivia();
+                    }
+
+                    self.depth -= 1;
                     self.builder.finish_node();
                 },
 
                 Event::Token { kind, span } => {
+                    self.eat_trivia();
                     self.token(kind, &self.source[span]);
                 },
+
+                Event::Missing { kind } => {
+                    self.builder.token(kind.into(), "");
+                },
             }
         }
 
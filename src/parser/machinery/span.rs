@@ -4,15 +4,22 @@ use std::{
     ops::{self, Index},
 };
 
+use crate::parser::source::FileId;
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Span {
+    file: FileId,
     start: u32,
     end: u32,
 }
 
 impl Span {
-    pub fn new(start: u32, end: u32) -> Self {
-        Self { start, end }
+    pub fn new(file: FileId, start: u32, end: u32) -> Self {
+        Self { file, start, end }
+    }
+
+    pub fn file(self) -> FileId {
+        self.file
     }
 
     pub fn start(self) -> u32 {
@@ -23,7 +30,7 @@ impl Span {
         self.end
     }
 
-    pub fn from_range(range: ops::Range<usize>) -> Self {
+    pub fn from_range(file: FileId, range: ops::Range<usize>) -> Self {
         debug_assert_eq!(
             u32::try_from(range.start),
             Ok(range.start as u32),
@@ -39,7 +46,7 @@ impl Span {
             u32::MAX,
         );
 
-        Self::new(range.start as u32, range.end as u32)
+        Self::new(file, range.start as u32, range.end as u32)
     }
 
     pub fn range(self) -> ops::Range<usize> {
@@ -48,10 +55,10 @@ impl Span {
 }
 
 impl ariadne::Span for Span {
-    type SourceId = ();
+    type SourceId = FileId;
 
     fn source(&self) -> &Self::SourceId {
-        &()
+        &self.file
     }
 
     fn start(&self) -> usize {
@@ -80,6 +87,8 @@ impl From<Span> for ops::Range<u32> {
 
 impl Debug for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.file, f)?;
+        f.write_str("@")?;
         Debug::fmt(&self.start, f)?;
         f.write_str("..")?;
         Debug::fmt(&self.end, f)
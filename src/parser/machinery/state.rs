@@ -1,40 +1,74 @@
 use std::ops::Not;
 
-use cstree::{GreenNode, NodeCache};
-use logos::Logos;
+use cstree::{interning::Interner, GreenNode, NodeCache};
 
-use super::{event::Event, kind::SyntaxKind, marker::Marker, sink::Sink, span::Span};
-use crate::T;
+use super::{
+    event::Event,
+    kind::SyntaxKind,
+    marker::{CompletedMarker, Marker},
+    scope::LabelScope,
+    sink::Sink,
+    span::Span,
+};
+use crate::{
+    parser::{
+        diagnostic::{Code, Diagnostic, Label},
+        options::{Feature, ParseOptions},
+        source::FileId,
+        token::lex,
+    },
+    T,
+};
 
-pub struct State<'cache, 'source> {
-    cache: &'cache mut NodeCache<'static>,
+pub struct State<'source> {
     tokens: Vec<(SyntaxKind, Span)>,
     cursor: usize,
     source: &'source str,
     events: Vec<Event>,
-    reports: Vec<ariadne::Report<Span>>,
+    reports: Vec<Diagnostic>,
+    label_scopes: Vec<LabelScope>,
+    recovery: Vec<&'static [SyntaxKind]>,
+    recovering: bool,
+    options: ParseOptions,
 }
 
-impl<'cache, 'source> State<'cache, 'source> {
-    pub fn new(cache: &'cache mut NodeCache<'static>, source: &'source str) -> Self {
+impl<'source> State<'source> {
+    pub fn new(file: FileId, source: &'source str, options: ParseOptions) -> Self {
         let mut tokens = Vec::new();
-        tokens.extend(
-            SyntaxKind::lexer(source)
-                .spanned()
-                .map(|(kind, range)| (kind, Span::from_range(range))),
-        );
+        let mut lex_errors = Vec::new();
+        for token in lex(source) {
+            let span = Span::from_range(file, token.range);
+            if token.kind == T![invalid] {
+                lex_errors.push((span, token.error.unwrap_or("unexpected character")));
+            }
+
+            // `goto` only became a keyword in Lua 5.2.
+            let kind = match token.kind {
+                T![goto] if !options.dialect.supports(Feature::Goto) => T![ident],
+                kind => kind,
+            };
+
+            tokens.push((kind, span));
+        }
 
-        tokens.push((T![eof], Span::from_range(0..0)));
+        tokens.push((T![eof], Span::from_range(file, source.len()..source.len())));
         let estimated_events = source.len() / 4;
 
-        State {
-            cache,
+        let mut state = State {
             tokens,
             cursor: 0,
             source,
             events: Vec::with_capacity(estimated_events),
             reports: Vec::new(),
-        }
+            label_scopes: Vec::new(),
+            recovery: Vec::new(),
+            recovering: false,
+            options,
+        };
+
+        state.report_lex_errors(&lex_errors);
+        state.skip_trivia();
+        state
     }
 
     pub fn at(&self) -> SyntaxKind {
@@ -45,13 +79,24 @@ impl<'cache, 'source> State<'cache, 'source> {
         self.tokens[self.cursor + 1..]
             .iter()
             .find_map(|(t, _)| t.is_trivia().not().then(|| *t))
-            .unwrap()
+            .unwrap_or(T![eof])
     }
 
     pub fn span(&self) -> Span {
         self.tokens[self.cursor].1
     }
 
+    /// Returns the span from `start` to the end of the last token eaten.
+    pub fn span_since(&self, start: Span) -> Span {
+        let end = self.tokens[..self.cursor]
+            .iter()
+            .rev()
+            .find(|(t, _)| !t.is_trivia())
+            .map_or(start.start(), |(_, span)| span.end());
+
+        Span::new(start.file(), start.start(), end.max(start.start()))
+    }
+
     pub fn start(&mut self) -> Marker {
         let pos = self.events.len();
         self.events.push(Event::tombstone());
@@ -65,32 +110,50 @@ impl<'cache, 'source> State<'cache, 'source> {
     pub fn expect(&mut self, kind: SyntaxKind) -> bool {
         if self.at() == kind {
             self.bump();
+            self.recovering = false;
             true
         } else {
-            self.report(
-                self.new_error()
-                    .with_message("unexpected token")
-                    .with_label(self.new_label().with_message(format!(
-                        "expected token {} but found {}",
-                        kind,
-                        self.at()
-                    )))
-                    .finish(),
+            let error = Diagnostic::error(
+                Code::UnexpectedToken,
+                "unexpected token",
+                self.new_label(format!("expected token {} but found {}", kind, self.at())),
             );
+
+            self.syntax_error(error);
+            self.missing(Some(kind));
             false
         }
     }
 
-    pub fn report(&mut self, error: ariadne::Report<Span>) {
+    // Inserts a node standing in for a token or, if no kind is given, for an
+    // expression the parser expected but didn't find.
+    pub fn missing(&mut self, kind: Option<SyntaxKind>) -> CompletedMarker {
+        let marker = self.start();
+        if let Some(kind) = kind {
+            self.events.push(Event::Missing { kind });
+        }
+
+        marker.complete(self, T![missing])
+    }
+
+    pub fn report(&mut self, error: Diagnostic) {
         self.reports.push(error);
     }
 
-    pub fn new_error(&self) -> ariadne::ReportBuilder<Span> {
-        ariadne::Report::build(ariadne::ReportKind::Error, (), self.span().start() as usize)
+    /// Reports a syntax error unless the parser is still recovering from a
+    /// previous one. Recovery ends once an expected token is found, so a single
+    /// mistake doesn't produce a cascade of follow-on errors.
+    pub fn syntax_error(&mut self, error: Diagnostic) {
+        if !self.recovering {
+            self.report(error);
+        }
+
+        self.recovering = true;
     }
 
-    pub fn new_label(&self) -> ariadne::Label<Span> {
-        ariadne::Label::new(self.span())
+    /// Creates a label pointing at the current token.
+    pub fn new_label(&self, message: impl Into<String>) -> Label {
+        Label::new(self.span(), message)
     }
 
     fn bump(&mut self) {
@@ -100,26 +163,165 @@ impl<'cache, 'source> State<'cache, 'source> {
         });
 
         self.cursor += 1;
+        self.skip_trivia();
+    }
+
+    // Trivia never produces events, the sink picks it up from the token list
+    // when building the tree.
+    fn skip_trivia(&mut self) {
+        while self.tokens[self.cursor].0.is_trivia() {
+            self.cursor += 1;
+        }
     }
 
     pub fn source(&self, span: Span) -> &str {
         &self.source[span]
     }
 
-    pub fn error_eat_until(&mut self, one_of: &[SyntaxKind]) -> Span {
+    pub fn error_eat_until(&mut self, one_of: &[SyntaxKind]) {
         let marker = self.start();
-        let mut last_span = self.span();
-        while !one_of.contains(&self.at()) {
+        let mut eaten = false;
+        while !one_of.contains(&self.at()) && !self.at_recovery() {
             self.bump();
-            last_span = self.span();
+            eaten = true;
         }
 
-        marker.complete(self, T![invalid]);
-        last_span
+        if eaten {
+            marker.complete(self, T![invalid]);
+        } else {
+            marker.abandon(self);
+        }
+    }
+
+    /// Registers the tokens the construct being parsed can resume at after an
+    /// error. Nested constructs stop skipping tokens at any of these, so an
+    /// error can't consume the closing token of an enclosing construct.
+    pub fn enter_recovery(&mut self, one_of: &'static [SyntaxKind]) {
+        self.recovery.push(one_of);
+    }
+
+    pub fn exit_recovery(&mut self) {
+        self.recovery.pop();
+    }
+
+    pub fn at_recovery(&self) -> bool {
+        let t = self.at();
+        t == T![eof] || self.recovery.iter().any(|one_of| one_of.contains(&t))
+    }
+
+    // Every invalid token is reported here so that the parser can skip over
+    // them without piling up further errors.
+    fn report_lex_errors(&mut self, lex_errors: &[(Span, &'static str)]) {
+        for &(span, message) in lex_errors {
+            let error = Diagnostic::error(Code::InvalidToken, message, Label::new(span, message));
+
+            self.report(error);
+        }
+    }
+
+    /// Reports the current token if it belongs to syntax that the selected
+    /// dialect doesn't support. The token is still parsed as usual.
+    pub fn require(&mut self, feature: Feature) {
+        let dialect = self.options.dialect;
+        if dialect.supports(feature) {
+            return;
+        }
+
+        let error = Diagnostic::error(
+            Code::UnsupportedSyntax,
+            format!(
+                "\"{}\" is not available in {}",
+                self.source(self.span()),
+                dialect
+            ),
+            self.new_label(feature.requirement()),
+        );
+
+        self.report(error);
+    }
+
+    pub fn enter_label_scope(&mut self, function: bool) {
+        self.label_scopes.push(LabelScope::new(function));
+    }
+
+    pub fn exit_label_scope(&mut self) {
+        let source = self.source;
+        let mut scope = self.label_scopes.pop().unwrap();
+        let unresolved: Vec<Span> = scope
+            .take_gotos()
+            .into_iter()
+            .filter(|target| {
+                !scope
+                    .labels()
+                    .iter()
+                    .any(|label| source[*label] == source[*target])
+            })
+            .collect();
+
+        // Gotos may jump to labels in enclosing blocks but never out of a function.
+        if let Some(parent) = self.label_scopes.last_mut() {
+            if !scope.is_function() {
+                for target in unresolved {
+                    parent.add_goto(target);
+                }
+
+                return;
+            }
+        }
+
+        for target in unresolved {
+            let error = Diagnostic::error(
+                Code::UndefinedLabel,
+                "undefined label",
+                Label::new(
+                    target,
+                    format!("no visible label \"{}\" for goto", &source[target]),
+                ),
+            );
+
+            self.report(error);
+        }
+    }
+
+    pub fn add_label(&mut self, name: Span) {
+        let source = self.source;
+        let scope = self.label_scopes.last_mut().unwrap();
+        let previous = scope
+            .labels()
+            .iter()
+            .copied()
+            .find(|label| source[*label] == source[name]);
+
+        if let Some(previous) = previous {
+            let error = Diagnostic::error(
+                Code::DuplicateLabel,
+                "duplicate label",
+                Label::new(
+                    name,
+                    format!(
+                        "label \"{}\" is already defined in this block",
+                        &source[name]
+                    ),
+                ),
+            )
+            .with_label(Label::new(previous, "previously defined here"));
+
+            self.report(error);
+        } else {
+            scope.add_label(name);
+        }
+    }
+
+    pub fn add_goto(&mut self, target: Span) {
+        self.label_scopes.last_mut().unwrap().add_goto(target);
     }
 
-    pub fn finish(self) -> (GreenNode, Vec<ariadne::Report<Span>>) {
-        let tree = Sink::new(self.cache, &self.tokens, self.events, self.source).finish();
+    /// Builds the tree, interning token texts with the cache's interner.
+    pub fn finish<I>(self, cache: &mut NodeCache<'_, I>) -> (GreenNode, Vec<Diagnostic>)
+    where
+        I: Interner,
+    {
+        let tree = Sink::new(cache, &self.tokens, self.events, self.source).finish();
         (tree, self.reports)
     }
 }
@@ -1,81 +1,217 @@
 mod assign;
+pub mod batch;
 mod control;
+pub mod diagnostic;
+pub mod emit;
 mod expr;
 mod function;
+mod incremental;
 mod item;
+pub mod line_index;
+pub mod literal;
 pub mod machinery;
+pub mod options;
+pub mod query;
+pub mod serialize;
 mod simple_expr;
+pub mod source;
 mod stmt;
-mod syntax;
+pub mod syntax;
 mod table;
+pub mod token;
+mod validate;
 
 use std::ops::{Deref, DerefMut};
 
-use cstree::NodeCache;
-use machinery::{span::Span, state::State};
+use cstree::{interning::Interner, NodeCache};
+use machinery::state::State;
 use syntax::SyntaxNode;
 
+pub use self::{
+    batch::{new_shared_interner, parse_all, ParsedFile, SharedInterner},
+    diagnostic::{Code, Diagnostic, Label, Severity, Suggestion},
+    emit::{emit, Format},
+    incremental::{reparse, TextEdit},
+    line_index::{LineCol, LineIndex},
+    literal::{decode_number, decode_string, validate_string, LiteralError, Number},
+    options::{Dialect, ParseOptions},
+    query::{Capture, Query, QueryError, QueryMatch},
+    serialize::{from_json, to_json, to_sexpr, TreeError},
+    source::{FileId, SourceDb},
+    token::{tokenize, Token, Tokens},
+};
 use crate::T;
 
-struct Parser<'cache, 'source> {
-    state: State<'cache, 'source>,
+struct Parser<'source> {
+    state: State<'source>,
 }
 
-impl<'cache, 'source> Parser<'cache, 'source> {
-    fn new(cache: &'cache mut NodeCache<'static>, source: &'source str) -> Self {
+impl<'source> Parser<'source> {
+    fn new(file: FileId, source: &'source str, options: ParseOptions) -> Self {
         Self {
-            state: State::new(cache, source),
+            state: State::new(file, source, options),
         }
     }
 
-    fn root(&mut self) {
+    // Wraps whatever `content` parses into the root node. The source is treated
+    // like the body of a function.
+    fn root(&mut self, content: impl FnOnce(&mut Self)) {
         let marker = self.start();
-        self.r_items();
+        self.enter_label_scope(true);
+        content(self);
+        self.r_end_of_input();
+        self.exit_label_scope();
         marker.complete(self, T![root]);
     }
 
-    fn run(mut self) -> (SyntaxNode, Vec<ariadne::Report<Span>>) {
-        self.root();
-        let (root, reports) = self.state.finish();
-        (SyntaxNode::new_root(root), reports)
+    fn run<I>(
+        mut self,
+        cache: &mut NodeCache<'_, I>,
+        content: impl FnOnce(&mut Self),
+    ) -> (SyntaxNode, Vec<Diagnostic>)
+    where
+        I: Interner,
+    {
+        self.root(content);
+        let file = self.span().file();
+        let (root, mut reports) = self.state.finish(cache);
+        let root = SyntaxNode::new_root(root);
+        reports.extend(validate::validate(&root, cache.interner(), file));
+        (root, reports)
     }
 }
 
-impl<'cache, 'source> Deref for Parser<'cache, 'source> {
-    type Target = State<'cache, 'source>;
+impl<'source> Deref for Parser<'source> {
+    type Target = State<'source>;
 
     fn deref(&self) -> &Self::Target {
         &self.state
     }
 }
 
-impl<'cache, 'source> DerefMut for Parser<'cache, 'source> {
+impl<'source> DerefMut for Parser<'source> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.state
     }
 }
 
-pub fn parse(
-    cache: &mut NodeCache<'static>,
-    source: &str,
-) -> (SyntaxNode, Vec<ariadne::Report<Span>>) {
-    Parser::new(cache, source).run()
+/// Parses a file from the database. All spans in the returned reports refer to
+/// `file`.
+///
+/// Syntax the selected dialect doesn't support is reported but otherwise parsed
+/// as usual. The only difference in the tree is that `goto` is a name in Lua
+/// 5.1. Errors the reference implementation reports at compile time, like a
+/// `break` outside of a loop or an assignment to a `<const>` local, are
+/// reported as well.
+pub fn parse<I>(
+    cache: &mut NodeCache<'_, I>,
+    db: &SourceDb,
+    file: FileId,
+    options: ParseOptions,
+) -> (SyntaxNode, Vec<Diagnostic>)
+where
+    I: Interner,
+{
+    Parser::new(file, db.text(file), options).run(cache, Parser::r_items)
+}
+
+/// Parses a file consisting of a single expression, which becomes the only
+/// child of the root. Anything following the expression is reported.
+pub fn parse_expr<I>(
+    cache: &mut NodeCache<'_, I>,
+    db: &SourceDb,
+    file: FileId,
+    options: ParseOptions,
+) -> (SyntaxNode, Vec<Diagnostic>)
+where
+    I: Interner,
+{
+    let content = |parser: &mut Parser| {
+        parser.r_expr();
+    };
+
+    Parser::new(file, db.text(file), options).run(cache, content)
+}
+
+/// Parses a file as a block, like the body of a function. The root contains a
+/// single statement list, so that the tree matches that of a block within a
+/// file.
+pub fn parse_block<I>(
+    cache: &mut NodeCache<'_, I>,
+    db: &SourceDb,
+    file: FileId,
+    options: ParseOptions,
+) -> (SyntaxNode, Vec<Diagnostic>)
+where
+    I: Interner,
+{
+    let content = |parser: &mut Parser| {
+        parser.r_block(&[]);
+    };
+
+    Parser::new(file, db.text(file), options).run(cache, content)
+}
+
+/// Parses a file consisting of a single statement, which becomes the only
+/// child of the root. Anything following the statement is reported.
+pub fn parse_stmt<I>(
+    cache: &mut NodeCache<'_, I>,
+    db: &SourceDb,
+    file: FileId,
+    options: ParseOptions,
+) -> (SyntaxNode, Vec<Diagnostic>)
+where
+    I: Interner,
+{
+    Parser::new(file, db.text(file), options).run(cache, Parser::r_single_stmt)
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use cstree::NodeCache;
+    use cstree::{GreenNode, NodeCache};
     use insta::assert_snapshot;
     use paste::paste;
 
-    use super::{parse, syntax::SyntaxNode};
+    use super::{
+        batch::{new_shared_interner, parse_all},
+        decode_number,
+        decode_string,
+        diagnostic::{Code, Diagnostic, Severity},
+        emit::{emit, Format},
+        machinery::{kind::SyntaxKind, span::Span},
+        options::Dialect,
+        parse,
+        parse_block,
+        parse_expr,
+        parse_stmt,
+        query::Query,
+        reparse,
+        serialize::{from_json, to_json, to_sexpr},
+        syntax::{AstNode, Expr, Root, Stmt, SyntaxNode, TableElem},
+        tokenize,
+        validate_string,
+        FileId,
+        LineCol,
+        LineIndex,
+        Number,
+        ParseOptions,
+        SourceDb,
+        TextEdit,
+    };
+    use crate::T;
 
     fn syntax_tree_debug(cache: &NodeCache<'static>, node: &SyntaxNode) -> String {
         node.debug(cache.interner(), true)
     }
 
+    fn parse_str(cache: &mut NodeCache<'static>, source: &str) -> (SyntaxNode, Vec<Diagnostic>) {
+        let mut db = SourceDb::new();
+        let file = db.add("test.lua", source);
+        parse(cache, &db, file, ParseOptions::default())
+    }
+
     macro_rules! parse_and_verify {
         ($name:ident, $path:literal) => {
             paste! {
@@ -83,9 +219,10 @@ mod tests {
                 fn [<parse_and_verify_ $name>]() {
                     let mut cache = NodeCache::new();
                     let source = fs::read_to_string($path).unwrap();
-                    let (syntax_tree, reports) = parse(&mut cache, &source);
+                    let (syntax_tree, reports) = parse_str(&mut cache, &source);
                     let syntax_tree_debug = syntax_tree_debug(&cache, &syntax_tree);
                     assert!(reports.is_empty());
+                    assert_eq!(syntax_tree.text(cache.interner()).to_string(), source);
                     assert_snapshot!(syntax_tree_debug);
                 }
             }
@@ -99,4 +236,758 @@ mod tests {
     parse_and_verify!(literal, "test-files/literal.lua");
     parse_and_verify!(comment, "test-files/comment.lua");
     parse_and_verify!(mixed, "test-files/mixed.lua");
+    parse_and_verify!(goto, "test-files/goto.lua");
+    parse_and_verify!(call_sugar, "test-files/call_sugar.lua");
+
+    #[test]
+    fn parse_malformed_input() {
+        let sources = [
+            "x = [[unterminated",
+            "--[==[ unterminated",
+            "-- comment without newline",
+            "function f(1, 2) end",
+            "t = { = }",
+            "if x then",
+            "do end end ) $",
+            "x = \"unterminated",
+            "local function",
+            "a.b:",
+            "--[[ ü ]] x = [=[ ä ]=]",
+        ];
+
+        for source in sources {
+            let mut cache = NodeCache::new();
+            let (syntax_tree, _) = parse_str(&mut cache, source);
+            assert_eq!(syntax_tree.text(cache.interner()).to_string(), source);
+        }
+    }
+
+    #[test]
+    fn reparse_matches_full_parse() {
+        let source = fs::read_to_string("test-files/function.lua").unwrap();
+        let mut db = SourceDb::new();
+        let file = db.add("function.lua", source.clone());
+        let span = |needle: &str, start: u32, end: u32| {
+            let offset = source.find(needle).unwrap() as u32;
+            Span::new(file, offset + start, offset + end)
+        };
+
+        // The bodies of the functions declared at the top level.
+        fn bodies(root: &SyntaxNode) -> Vec<&GreenNode> {
+            root.descendants()
+                .filter(|node| node.kind() == T![stmt_list])
+                .filter(|node| node.parent().map(SyntaxNode::kind) == Some(T![func_stmt]))
+                .map(SyntaxNode::green)
+                .collect()
+        }
+
+        // Edits inside a single body are reparsed in place, the others fall
+        // back to a full parse.
+        let edits = [
+            (TextEdit::new(span("a + b", 0, 1), "a * 3"), true),
+            (TextEdit::new(span("a - b", 2, 3), "+"), true),
+            (TextEdit::new(span("a - b", 5, 5), " + 1"), false),
+            (TextEdit::new(span("a * b", 0, 0), "end "), false),
+            (TextEdit::new(span("a / b", 0, 0), "--[[ "), false),
+        ];
+
+        let options = ParseOptions::default();
+        for (edit, in_place) in edits {
+            let mut new_source = source.clone();
+            new_source.replace_range(edit.span.range(), &edit.text);
+
+            // Separate caches, so that nodes are only shared if the old ones
+            // were reused.
+            let interner = new_shared_interner();
+            let mut cache = NodeCache::from_interner(&interner);
+            let (old_tree, old_reports) = parse(&mut cache, &db, file, options);
+            let mut cache = NodeCache::from_interner(&interner);
+            let (new_tree, reports) = reparse(
+                &mut cache,
+                file,
+                &old_tree,
+                &old_reports,
+                &source,
+                &[edit],
+                options,
+            );
+
+            let mut new_db = SourceDb::new();
+            let new_file = new_db.add("function.lua", new_source);
+            let mut cache = NodeCache::from_interner(&interner);
+            let (expected, expected_reports) = parse(&mut cache, &new_db, new_file, options);
+            assert_eq!(
+                new_tree.debug(&interner, true),
+                expected.debug(&interner, true)
+            );
+            assert_eq!(reports, expected_reports);
+
+            let shared = bodies(&old_tree)
+                .into_iter()
+                .zip(bodies(&new_tree))
+                .filter(|(old, new)| std::ptr::eq(*old, *new))
+                .count();
+
+            assert_eq!(shared, if in_place { 3 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn reparse_updates_reports() {
+        let source = "function f(a)\n    local b = a\n    return b\nend\nbreak\n::l:: ::l::\n";
+        let mut db = SourceDb::new();
+        let file = db.add("reports.lua", source);
+        let span = |needle: &str, start: u32, end: u32| {
+            let offset = source.find(needle).unwrap() as u32;
+            Span::new(file, offset + start, offset + end)
+        };
+
+        // Reports of the validator change within the body, the ones after it
+        // move. The goto can't be resolved without the enclosing scopes.
+        let edits = [
+            TextEdit::new(span("= a", 2, 3), "..."),
+            TextEdit::new(span("return", 0, 0), "break "),
+            TextEdit::new(span("return", 0, 0), "goto m "),
+        ];
+
+        let options = ParseOptions::default();
+        for edit in edits {
+            let mut new_source = source.to_owned();
+            new_source.replace_range(edit.span.range(), &edit.text);
+
+            let mut cache = NodeCache::new();
+            let (old_tree, old_reports) = parse(&mut cache, &db, file, options);
+            let (new_tree, reports) = reparse(
+                &mut cache,
+                file,
+                &old_tree,
+                &old_reports,
+                source,
+                &[edit],
+                options,
+            );
+
+            let mut new_db = SourceDb::new();
+            let new_file = new_db.add("reports.lua", new_source.as_str());
+            let (expected, expected_reports) = parse(&mut cache, &new_db, new_file, options);
+            assert_eq!(
+                syntax_tree_debug(&cache, &new_tree),
+                syntax_tree_debug(&cache, &expected)
+            );
+            assert_eq!(reports, expected_reports, "{}", new_source);
+        }
+    }
+
+    #[test]
+    fn report_bad_labels() {
+        let mut cache = NodeCache::new();
+        let source = "::a:: ::a:: goto b local function f() goto a end";
+        let (_, reports) = parse_str(&mut cache, source);
+        assert_eq!(reports.len(), 3);
+    }
+
+    #[test]
+    fn report_bad_attribs() {
+        let mut cache = NodeCache::new();
+        let (_, reports) = parse_str(&mut cache, "local x <foo> = 1");
+        assert_eq!(reports.len(), 1);
+
+        let (_, reports) = parse_str(&mut cache, "local a <close>, b <close> = f(), g()");
+        assert_eq!(reports.len(), 1);
+    }
+
+    #[test]
+    fn report_bad_field_access() {
+        let mut cache = NodeCache::new();
+        let (_, reports) = parse_str(&mut cache, "x = a.(1 + 2)");
+        assert!(!reports.is_empty());
+
+        let (_, reports) = parse_str(&mut cache, "x = a:b");
+        assert!(!reports.is_empty());
+    }
+
+    #[test]
+    fn parse_prefix_expressions() {
+        let cases = [
+            ("(a).b = 1", T![assign_stmt], 0),
+            ("(f or g)(x)", T![func_call], 0),
+            ("(\"x\"):rep(3)", T![method_call_expr], 0),
+            ("(t)[1], a.b = 1, 2", T![assign_stmt], 0),
+            ("f() = 1", T![assign_stmt], 1),
+            ("a, (b) = 1, 2", T![assign_stmt], 1),
+            ("a.b", T![field_expr], 1),
+            ("(f)", T![expr], 1),
+            ("x y = 1", T![ident], 1),
+            ("x = \"a\":rep(3)", T![assign_stmt], 1),
+            ("x = \"a\"[1]", T![assign_stmt], 1),
+            ("x = {}.n", T![assign_stmt], 1),
+            ("x = 1()", T![assign_stmt], 2),
+            ("print(\"a\" \"b\")", T![func_call], 1),
+        ];
+
+        for (source, kind, errors) in cases {
+            let mut cache = NodeCache::new();
+            let (syntax_tree, reports) = parse_str(&mut cache, source);
+            let first = syntax_tree.children().next().map(|child| child.kind());
+            assert_eq!(first, Some(kind), "{}", source);
+            assert_eq!(reports.len(), errors, "{}", source);
+        }
+    }
+
+    #[test]
+    fn report_invalid_semantics() {
+        let cases = [
+            ("while x do if y then break end end", 0),
+            ("break", 1),
+            ("for i = 1, 2 do f(function() break end) end", 1),
+            ("repeat local x = f() until x", 0),
+            ("do return 1; end", 0),
+            ("return 1 x = 2", 1),
+            ("do return; ; end", 1),
+            ("local x <const> = 1 x = 2", 1),
+            ("local x <close> = f() function x() end", 1),
+            ("local x <const> = 1 local x = 2 x = 3", 0),
+            ("local x <const> = 1 do local function x() x = 2 end end", 0),
+            ("local x <const> = 1 for x = 1, 2 do x = 3 end", 0),
+            (
+                "local x <const> = 1 repeat local y = 2 y = 3 until (function() x = y end)()",
+                1,
+            ),
+            ("print(...) local function f(a, ...) return ... end", 0),
+            ("function f(a) return ... end", 1),
+            ("local a <close>, b <const>, c <close> = f(), 1, g()", 1),
+        ];
+
+        for (source, errors) in cases {
+            let mut cache = NodeCache::new();
+            let (_, reports) = parse_str(&mut cache, source);
+            assert_eq!(reports.len(), errors, "{}", source);
+        }
+    }
+
+    #[test]
+    fn report_unsupported_syntax() {
+        let source = "x = a // b ~ c\n::l:: goto l\nlocal y <const> = 0xffULL";
+        let mut db = SourceDb::new();
+        let file = db.add("dialect.lua", source);
+        let expected = [
+            (Dialect::Lua51, 7),
+            (Dialect::Lua52, 4),
+            (Dialect::Lua53, 2),
+            (Dialect::Lua54, 1),
+            (Dialect::LuaJit, 3),
+        ];
+
+        for (dialect, count) in expected {
+            let mut cache = NodeCache::new();
+            let (_, reports) = parse(&mut cache, &db, file, ParseOptions::new(dialect));
+            assert_eq!(reports.len(), count, "{}", dialect);
+        }
+
+        // Before Lua 5.2, `goto` is an ordinary name.
+        let file = db.add("names.lua", "local goto = 1\nt.goto = f");
+        for (dialect, valid) in [(Dialect::Lua51, true), (Dialect::Lua52, false)] {
+            let mut cache = NodeCache::new();
+            let (_, reports) = parse(&mut cache, &db, file, ParseOptions::new(dialect));
+            assert_eq!(reports.is_empty(), valid, "{}", dialect);
+        }
+
+        // So a goto statement is a name followed by another one, rather than
+        // syntax Lua 5.1 lacks.
+        let file = db.add("goto.lua", "goto foo");
+        let mut cache = NodeCache::new();
+        let options = ParseOptions::new(Dialect::Lua51);
+        let (_, reports) = parse(&mut cache, &db, file, options);
+        let reports: Vec<_> = reports
+            .iter()
+            .map(|report| (report.code, report.span().range()))
+            .collect();
+        assert_eq!(
+            reports,
+            [(Code::NotAStatement, 0..4), (Code::NotAStatement, 5..8)]
+        );
+    }
+
+    #[test]
+    fn convert_line_columns() {
+        let source = "x = 1\r\ns = \"ä😀\" y\rz";
+        let index = LineIndex::new(source);
+        assert_eq!(index.line_count(), 3);
+
+        let y = source.find('y').unwrap() as u32;
+        assert_eq!(index.line_col(y), LineCol::new(1, 13));
+        assert_eq!(index.line_col_utf16(y), LineCol::new(1, 10));
+        assert_eq!(index.offset(LineCol::new(1, 13)), Some(y));
+        assert_eq!(index.offset_utf16(LineCol::new(1, 10)), Some(y));
+
+        let z = source.find('z').unwrap() as u32;
+        assert_eq!(index.line_col(z), LineCol::new(2, 0));
+        assert_eq!(index.line_col(5), LineCol::new(0, 5));
+        assert_eq!(index.offset(LineCol::new(3, 0)), None);
+    }
+
+    #[test]
+    fn render_reports_per_file() {
+        let mut db = SourceDb::new();
+        let first = db.add("first.lua", "x = 1\ny = )");
+        let second = db.add("second.lua", "goto nowhere");
+
+        let mut cache = NodeCache::new();
+        let mut output = Vec::new();
+        for file in [first, second] {
+            let (_, reports) = parse(&mut cache, &db, file, ParseOptions::default());
+            assert_eq!(reports.len(), 1);
+            for report in reports {
+                report.write(&db, &mut output).unwrap();
+            }
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("first.lua"));
+        assert!(output.contains("second.lua"));
+    }
+
+    #[test]
+    fn inspect_diagnostics() {
+        let mut cache = NodeCache::new();
+        let (_, reports) = parse_str(&mut cache, "x = )");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].code, Code::ExpectedExpression);
+        assert_eq!(reports[0].code.as_str(), "E0005");
+        assert_eq!(reports[0].severity, Severity::Error);
+        assert_eq!(reports[0].message, "expected an expression");
+        assert_eq!(reports[0].span().range(), 4..5);
+
+        let source = "local x <const> = 1\nx = 2";
+        let (_, reports) = parse_str(&mut cache, source);
+        let Diagnostic {
+            code,
+            primary,
+            secondary,
+            suggestions,
+            ..
+        } = &reports[0];
+
+        assert_eq!(*code, Code::AssignToConst);
+        assert_eq!(&source[primary.span], "x");
+        assert_eq!(&source[secondary[0].span], "<const>");
+        assert_eq!(&source[suggestions[0].span], "<const>");
+        assert_eq!(suggestions[0].replacement, "");
+
+        let codes: Vec<&str> = Code::ALL.iter().map(|code| code.as_str()).collect();
+        assert!(codes.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn emit_diagnostics() {
+        let mut db = SourceDb::new();
+        let first = db.add("first.lua", "x = 1\ny = )");
+        let second = db.add("second.lua", "local x <const> = \"ä\" x = 2");
+
+        let mut cache = NodeCache::new();
+        let mut diagnostics = Vec::new();
+        for file in [first, second] {
+            let (_, reports) = parse(&mut cache, &db, file, ParseOptions::default());
+            diagnostics.extend(reports);
+        }
+
+        let render = |format| {
+            let mut output = Vec::new();
+            emit(format, &db, &diagnostics, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            render(Format::Gcc),
+            "first.lua:2:5: error: expected an expression [E0005]\nsecond.lua:1:24: error: \
+             attempt to assign to const variable \"x\" [E0018]\nsecond.lua:1:9: note: declared \
+             read-only here\nsecond.lua:1:9: note: remove the attribute to make the variable \
+             mutable\n"
+        );
+
+        let lines: Vec<serde_json::Value> = render(Format::JsonLines)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["code"], "E0005");
+        assert_eq!(lines[0]["primary"]["location"]["start"]["line"], 2);
+        assert_eq!(lines[1]["suggestions"][0]["replacement"], "");
+
+        let sarif: serde_json::Value = serde_json::from_str(&render(Format::Sarif)).unwrap();
+        let results = &sarif["runs"][0]["results"];
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(results[1]["ruleId"], "E0018");
+        let region = &results[1]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startColumn"], 23);
+        assert_eq!(region["byteOffset"], 23);
+
+        assert!(render(Format::Human).contains("E0005"));
+    }
+
+    #[test]
+    fn recover_from_syntax_errors() {
+        let sources = [
+            "function f() print(1 end x = 2",
+            "f(1 2) x = 2",
+            "local t = { 1, = } x = 2",
+            "for i = 1, 10 print(i) end x = 2",
+        ];
+
+        for source in sources {
+            let mut cache = NodeCache::new();
+            let (syntax_tree, reports) = parse_str(&mut cache, source);
+            assert_eq!(reports.len(), 1, "{}", source);
+            assert_eq!(syntax_tree.text(cache.interner()).to_string(), source);
+
+            let root = Root::cast(syntax_tree).unwrap();
+            assert!(matches!(root.stmts().last(), Some(Stmt::Assign(_))));
+        }
+    }
+
+    #[test]
+    fn typed_ast_accessors() {
+        let mut cache = NodeCache::new();
+        let source = fs::read_to_string("test-files/if.lua").unwrap();
+        let (syntax_tree, _) = parse_str(&mut cache, &source);
+        let root = Root::cast(syntax_tree).unwrap();
+
+        let stmts: Vec<_> = root.stmts().collect();
+        assert_eq!(stmts.len(), 3);
+
+        let if_stmt = match &stmts[2] {
+            Stmt::If(if_stmt) => if_stmt,
+            stmt => panic!("expected an if statement, got {:?}", stmt),
+        };
+
+        let condition = match if_stmt.condition() {
+            Some(Expr::BinaryOp(condition)) => condition,
+            expr => panic!("expected a binary operation, got {:?}", expr),
+        };
+
+        assert_eq!(condition.op().unwrap().kind(), T![<]);
+        assert!(matches!(condition.lhs(), Some(Expr::Ident(_))));
+        assert!(matches!(condition.rhs(), Some(Expr::Literal(_))));
+
+        let else_if = if_stmt.else_chain().unwrap().else_if().unwrap();
+        assert!(else_if.block().is_some());
+        assert!(else_if.else_chain().unwrap().block().is_some());
+    }
+
+    #[test]
+    fn typed_ast_error_trees() {
+        let mut cache = NodeCache::new();
+        let source = "x = $ + 1\nx = * 2\nx = t[]\nfor i = , 2 do end\nt = { [] = 1 }";
+        let (syntax_tree, reports) = parse_str(&mut cache, source);
+        assert!(!reports.is_empty());
+
+        let root = Root::cast(syntax_tree).unwrap();
+        let values: Vec<_> = root
+            .stmts()
+            .filter_map(|stmt| match stmt {
+                Stmt::Assign(assign) => assign.values().into_iter().next(),
+                _ => None,
+            })
+            .collect();
+
+        for value in &values[..2] {
+            let op = match value {
+                Expr::BinaryOp(op) => op,
+                expr => panic!("expected a binary operation, got {:?}", expr),
+            };
+
+            assert!(op.lhs().is_none());
+            assert!(matches!(op.rhs(), Some(Expr::Literal(_))));
+        }
+
+        let index = match &values[2] {
+            Expr::Index(index) => index,
+            expr => panic!("expected an index, got {:?}", expr),
+        };
+
+        assert!(matches!(index.expr(), Some(Expr::Ident(_))));
+        assert!(index.index().is_none());
+
+        let elem = match &values[3] {
+            Expr::Table(table) => match table.elems().next() {
+                Some(TableElem::Generic(elem)) => elem,
+                elem => panic!("expected a generic element, got {:?}", elem),
+            },
+            expr => panic!("expected a table, got {:?}", expr),
+        };
+
+        assert!(elem.key().is_none());
+        assert!(matches!(elem.value(), Some(Expr::Literal(_))));
+
+        let for_num = root
+            .stmts()
+            .find_map(|stmt| match stmt {
+                Stmt::ForNum(for_num) => Some(for_num),
+                _ => None,
+            })
+            .unwrap();
+
+        assert!(for_num.start().is_none());
+        assert!(matches!(for_num.end(), Some(Expr::Literal(_))));
+        assert!(for_num.step().is_none());
+    }
+
+    #[test]
+    fn decode_string_literals() {
+        let decoded = decode_string("\"a\\x41\\65\\u{48}\\z   b\\\nc\"").unwrap();
+        assert_eq!(decoded, b"aAAHb\nc");
+
+        let decoded = decode_string("[==[\nfirst\r\nsecond]]==]").unwrap();
+        assert_eq!(decoded, b"first\nsecond]");
+
+        let decoded = decode_string("'\\u{20AC}\\u{7FFFFFFF}'").unwrap();
+        assert_eq!(decoded, b"\xe2\x82\xac\xfd\xbf\xbf\xbf\xbf\xbf");
+
+        let errors = decode_string("'\\q \\300'").unwrap_err();
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| (e.range.clone(), e.message))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (1..3, "invalid escape sequence"),
+                (4..8, "decimal escape too large"),
+            ]
+        );
+
+        for source in ["'\\q \\300'", "'\\u{48}\\z  x'", "[[\\q]]", "\"open"] {
+            assert_eq!(validate_string(source), decode_string(source).map(drop));
+        }
+
+        let mut cache = NodeCache::new();
+        let (syntax_tree, reports) = parse_str(&mut cache, "x = '\\q'");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(syntax_tree.text(cache.interner()).to_string(), "x = '\\q'");
+    }
+
+    #[test]
+    fn skip_file_header() {
+        let mut cache = NodeCache::new();
+        let source = "\u{feff}#!/usr/bin/env lua\nprint(#arg)\n";
+        let (syntax_tree, reports) = parse_str(&mut cache, source);
+        assert!(reports.is_empty());
+        assert_eq!(syntax_tree.text(cache.interner()).to_string(), source);
+
+        let kinds: Vec<_> = syntax_tree
+            .children_with_tokens()
+            .take(3)
+            .map(|element| element.kind())
+            .collect();
+        assert_eq!(kinds, [T![bom], T![shebang], T![whitespace]]);
+
+        let (_, reports) = parse_str(&mut cache, "x = 1\n#!/usr/bin/env lua\n");
+        assert!(!reports.is_empty());
+    }
+
+    #[test]
+    fn decode_numeric_literals() {
+        let cases = [
+            ("3", Number::Int(3)),
+            ("0xA", Number::Int(10)),
+            ("0Xff", Number::Int(255)),
+            ("9223372036854775807", Number::Int(i64::MAX)),
+            ("9223372036854775808", Number::Float(9223372036854775808.0)),
+            ("0xffffffffffffffff", Number::Int(-1)),
+            ("0x10000000000000001", Number::Int(1)),
+            (".5", Number::Float(0.5)),
+            ("5.", Number::Float(5.0)),
+            ("1e10", Number::Float(1e10)),
+            ("3.25E-2", Number::Float(0.0325)),
+            ("0x1p4", Number::Float(16.0)),
+            ("0x.8", Number::Float(0.5)),
+            ("0xA.8p-1", Number::Float(5.25)),
+            ("0xffULL", Number::Int(255)),
+        ];
+
+        for (text, value) in cases {
+            assert_eq!(decode_number(text), Some(value), "{}", text);
+        }
+
+        let mut cache = NodeCache::new();
+        for source in ["x = 3..2", "x = 0xg", "x = 1e+", "x = 5abc"] {
+            let (_, reports) = parse_str(&mut cache, source);
+            assert_eq!(reports.len(), 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn serialize_syntax_trees() {
+        for entry in fs::read_dir("test-files").unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+            let mut cache = NodeCache::new();
+            let (syntax_tree, _) = parse_str(&mut cache, &source);
+
+            let json = to_json(&syntax_tree, cache.interner());
+            let rebuilt = from_json(&mut cache, &json).unwrap();
+            assert_eq!(
+                syntax_tree_debug(&cache, &rebuilt),
+                syntax_tree_debug(&cache, &syntax_tree)
+            );
+        }
+
+        let mut cache = NodeCache::new();
+        let (syntax_tree, _) = parse_str(&mut cache, "local x = -1 -- one");
+        assert_eq!(
+            to_sexpr(&syntax_tree, cache.interner()),
+            "(Root (DeclStmt (Local \"local\") (DeclTarget (Ident \"x\")) (Assign \"=\") \
+             (PrefixOp (Minus \"-\") (LiteralExpr (Int \"1\")))))"
+        );
+
+        for json in [
+            r#"{"kind": "Ident", "text": "x"}"#,
+            r#"{"kind": "Root", "children": [{"kind": "Nope", "text": ""}]}"#,
+            r#"{"kind": "Root", "children": [{"kind": "__LAST", "text": ""}]}"#,
+            r#"{"kind": "Root", "children": [{"kind": "Eof", "text": ""}]}"#,
+            r#"{"kind": "Tombstone", "children": []}"#,
+            r#"{"kind": "Root", "children": [{"kind": "Ident", "span": [0, 2], "text": "x"}]}"#,
+        ] {
+            assert!(from_json(&mut cache, json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn run_queries() {
+        let mut cache = NodeCache::new();
+        let source = fs::read_to_string("test-files/call_sugar.lua").unwrap();
+        let (syntax_tree, _) = parse_str(&mut cache, &source);
+
+        // Calls to `require` with a string literal.
+        let query = Query::new(
+            r#"(FuncCall (Ident) @callee (_ (LiteralExpr (String)) @path)
+                (#eq? @callee "require"))"#,
+        )
+        .unwrap();
+
+        let matches = query.matches(&syntax_tree, cache.interner());
+        assert_eq!(matches.len(), 1);
+        let path = matches[0].captures_named("path").next().unwrap();
+        assert_eq!(&source[path.range.clone()], "\"mod\"");
+
+        // Local declarations without initialisers.
+        let source = "local a, b\nlocal c = 1\nlocal d <const>";
+        let (syntax_tree, _) = parse_str(&mut cache, source);
+        let query = Query::new("(DeclStmt \"local\" (DeclTarget)+ @name !Assign)").unwrap();
+        let names: Vec<_> = query
+            .matches(&syntax_tree, cache.interner())
+            .iter()
+            .flat_map(|found| found.captures_named("name"))
+            .map(|capture| &source[capture.range.clone()])
+            .collect();
+        assert_eq!(names, ["a", "b", "d <const>"]);
+
+        let query = Query::new("(Ident) @id (#any-of? @id \"b\" \"c\") ; comment").unwrap();
+        assert_eq!(query.matches(&syntax_tree, cache.interner()).len(), 2);
+
+        for (query, error) in [
+            ("(Nope)", "unknown kind `Nope`"),
+            ("(Ident", "unclosed pattern"),
+            ("(Ident)+", "quantifiers are only allowed on child patterns"),
+            ("(Ident (#eq? @x \"a\"))", "unknown capture `x`"),
+            ("(Ident) @x (#eq? @x)", "invalid arguments to `#eq?`"),
+        ] {
+            assert_eq!(Query::new(query).unwrap_err().message, error, "{}", query);
+        }
+    }
+
+    #[test]
+    fn parse_files_in_parallel() {
+        let mut db = SourceDb::new();
+        for entry in fs::read_dir("test-files").unwrap() {
+            let path = entry.unwrap().path();
+            let source = fs::read_to_string(&path).unwrap();
+            db.add(path.display().to_string(), source);
+        }
+
+        let interner = new_shared_interner();
+        let parsed = parse_all(&db, &interner, ParseOptions::default());
+        assert_eq!(parsed.len(), db.files().count());
+
+        for (parsed, file) in parsed.iter().zip(db.files()) {
+            assert_eq!(parsed.file, file);
+            assert!(parsed.reports.is_empty());
+
+            let mut cache = NodeCache::new();
+            let (syntax_tree, _) = parse(&mut cache, &db, file, ParseOptions::default());
+            assert_eq!(
+                parsed.tree.debug(&interner, true),
+                syntax_tree_debug(&cache, &syntax_tree)
+            );
+        }
+    }
+
+    #[test]
+    fn tokenize_source() {
+        let source = "#!lua\nx = 'a\\q' -- c\ny = \"open\n";
+        let tokens: Vec<_> = tokenize(source)
+            .map(|token| (token.kind, token.text, token.error))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                (T![shebang], "#!lua", None),
+                (T![whitespace], "\n", None),
+                (T![ident], "x", None),
+                (T![whitespace], " ", None),
+                (T![=], "=", None),
+                (T![whitespace], " ", None),
+                (T![string], "'a\\q'", Some("invalid escape sequence")),
+                (T![whitespace], " ", None),
+                (T![comment], "-- c", None),
+                (T![whitespace], "\n", None),
+                (T![ident], "y", None),
+                (T![whitespace], " ", None),
+                (T![=], "=", None),
+                (T![whitespace], " ", None),
+                (T![invalid], "\"open", Some("unterminated string")),
+                (T![whitespace], "\n", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_snippets() {
+        type Entry = fn(
+            &mut NodeCache<'static>,
+            &SourceDb,
+            FileId,
+            ParseOptions,
+        ) -> (SyntaxNode, Vec<Diagnostic>);
+
+        let cases: [(Entry, &str, Option<SyntaxKind>, usize); 9] = [
+            (parse_expr, "level * 2 + 1", Some(T![bin_op]), 0),
+            (parse_expr, "f(x) -- call\n", Some(T![func_call]), 0),
+            (parse_expr, "1 + 2 3", Some(T![bin_op]), 1),
+            (parse_expr, "", Some(T![missing]), 1),
+            (
+                parse_block,
+                "local x = ... return x",
+                Some(T![stmt_list]),
+                0,
+            ),
+            (parse_block, "return x end", Some(T![stmt_list]), 1),
+            (parse_stmt, "x = 1", Some(T![assign_stmt]), 0),
+            (parse_stmt, "x = 1 y = 2", Some(T![assign_stmt]), 1),
+            (parse_stmt, "", None, 1),
+        ];
+
+        for (entry, source, kind, errors) in cases {
+            let mut cache = NodeCache::new();
+            let mut db = SourceDb::new();
+            let file = db.add("snippet.lua", source);
+            let (syntax_tree, reports) = entry(&mut cache, &db, file, ParseOptions::default());
+
+            let first = syntax_tree.children().next().map(|child| child.kind());
+            assert_eq!(first, kind, "{}", source);
+            assert_eq!(reports.len(), errors, "{}", source);
+            assert_eq!(syntax_tree.text(cache.interner()).to_string(), source);
+        }
+    }
 }
@@ -0,0 +1,75 @@
+use std::fmt::{self, Display};
+
+/// The Lua version whose grammar the source is checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    Lua51,
+    Lua52,
+    Lua53,
+    Lua54,
+    LuaJit,
+}
+
+impl Dialect {
+    pub fn supports(self, feature: Feature) -> bool {
+        match feature {
+            Feature::IntegerDivision | Feature::BitwiseOperators => {
+                matches!(self, Dialect::Lua53 | Dialect::Lua54)
+            },
+            Feature::Goto => self != Dialect::Lua51,
+            Feature::Attributes => self == Dialect::Lua54,
+            Feature::IntegerSuffixes => self == Dialect::LuaJit,
+        }
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect::Lua54
+    }
+}
+
+impl Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dialect::Lua51 => "Lua 5.1",
+            Dialect::Lua52 => "Lua 5.2",
+            Dialect::Lua53 => "Lua 5.3",
+            Dialect::Lua54 => "Lua 5.4",
+            Dialect::LuaJit => "LuaJIT",
+        })
+    }
+}
+
+/// Syntax that only some dialects accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    IntegerDivision,
+    BitwiseOperators,
+    Goto,
+    Attributes,
+    IntegerSuffixes,
+}
+
+impl Feature {
+    pub fn requirement(self) -> &'static str {
+        match self {
+            Feature::IntegerDivision => "integer division requires Lua 5.3 or later",
+            Feature::BitwiseOperators => "bitwise operators require Lua 5.3 or later",
+            Feature::Goto => "goto and labels require Lua 5.2 or later, or LuaJIT",
+            Feature::Attributes => "variable attributes require Lua 5.4",
+            Feature::IntegerSuffixes => "integer suffixes require LuaJIT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    pub dialect: Dialect,
+}
+
+impl ParseOptions {
+    pub fn new(dialect: Dialect) -> Self {
+        Self { dialect }
+    }
+}
@@ -0,0 +1,606 @@
+//! Pattern matching over syntax trees with queries in the style of
+//! tree-sitter.
+//!
+//! A query is a list of patterns. `(Kind children...)` matches an element of
+//! the given kind whose children match the child patterns in order, other
+//! children may appear between them. `(_ children...)` and `_` match any
+//! element that isn't trivia, and `"text"` matches a token with that text.
+//!
+//! Child patterns may be followed by a quantifier, `?`, `*` or `+`, and any
+//! pattern by captures like `@name`. Inside the parentheses, `!Kind` requires
+//! that no child has the given kind. Predicates filter matches by the text of
+//! their captures, and go either inside the parentheses of a pattern or right
+//! after a top-level pattern:
+//!
+//! - `(#eq? @a "text")` and `(#eq? @a @b)`, and their negation `#not-eq?`.
+//! - `(#any-of? @a "one" "two")`.
+//!
+//! Comments start with `;` and run until the end of the line.
+
+use std::{fmt, ops::Range};
+
+use cstree::{interning::Resolver, NodeOrToken};
+
+use super::{
+    machinery::kind::SyntaxKind,
+    syntax::{kind_from_name, SyntaxElement, SyntaxNode, SyntaxToken},
+};
+
+type Element<'a> = NodeOrToken<&'a SyntaxNode, &'a SyntaxToken>;
+
+/// A problem found while compiling a query. The range is relative to the start
+/// of the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.range.start, self.range.end
+        )
+    }
+}
+
+/// An element captured by a match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub name: String,
+    pub element: SyntaxElement,
+    pub range: Range<usize>,
+}
+
+/// A successful match of one of the patterns of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryMatch {
+    /// The index of the pattern within the query.
+    pub pattern: usize,
+    pub captures: Vec<Capture>,
+}
+
+impl QueryMatch {
+    pub fn captures_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Capture> {
+        self.captures
+            .iter()
+            .filter(move |capture| capture.name == name)
+    }
+}
+
+/// A compiled query, which can be run over any number of trees.
+#[derive(Debug, Clone)]
+pub struct Query {
+    patterns: Vec<TopPattern>,
+    capture_names: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TopPattern {
+    pattern: Pattern,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    head: Head,
+    children: Vec<Pattern>,
+    absent: Vec<SyntaxKind>,
+    quantifier: Quantifier,
+    captures: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Head {
+    Kind(SyntaxKind),
+    Text(String),
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quantifier {
+    One,
+    Optional,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+impl Quantifier {
+    fn bounds(self) -> (usize, usize) {
+        match self {
+            Quantifier::One => (1, 1),
+            Quantifier::Optional => (0, 1),
+            Quantifier::ZeroOrMore => (0, usize::MAX),
+            Quantifier::OneOrMore => (1, usize::MAX),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Eq {
+        capture: usize,
+        value: Argument,
+        negate: bool,
+    },
+    AnyOf {
+        capture: usize,
+        values: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Argument {
+    Capture(usize),
+    Text(String),
+}
+
+impl Query {
+    pub fn new(source: &str) -> Result<Self, QueryError> {
+        let mut parser = QueryParser {
+            source,
+            pos: 0,
+            capture_names: Vec::new(),
+            pattern_captures: Vec::new(),
+        };
+
+        let mut patterns = Vec::new();
+        while parser.peek().is_some() {
+            parser.pattern_captures.clear();
+            let start = parser.pos;
+            let mut predicates = Vec::new();
+            let pattern = parser.pattern(&mut predicates)?;
+
+            if pattern.quantifier != Quantifier::One {
+                return Err(parser.error(start, "quantifiers are only allowed on child patterns"));
+            }
+
+            while parser.at_predicate() {
+                predicates.push(parser.predicate()?);
+            }
+
+            patterns.push(TopPattern {
+                pattern,
+                predicates,
+            });
+        }
+
+        Ok(Self {
+            patterns,
+            capture_names: parser.capture_names,
+        })
+    }
+
+    pub fn capture_names(&self) -> &[String] {
+        &self.capture_names
+    }
+
+    /// Runs the query over `root` and all of its descendants, in preorder. Each
+    /// element matches each pattern at most once.
+    ///
+    /// Child patterns are matched by backtracking over every way of assigning
+    /// them to children, without memoization. On a node with `n` children, `k`
+    /// child patterns that fail to match can take in the order of `n^k` steps,
+    /// and a `*` or `+` pattern may try every subset of the children, which is
+    /// exponential in `n`. Quantified and wildcard child patterns on wide
+    /// nodes, like long statement lists, should be avoided or combined with
+    /// a specific parent pattern.
+    pub fn matches<I>(&self, root: &SyntaxNode, resolver: &I) -> Vec<QueryMatch>
+    where
+        I: Resolver + ?Sized,
+    {
+        let matcher = Matcher { resolver };
+
+        let mut matches = Vec::new();
+        for element in root.descendants_with_tokens() {
+            for (index, top) in self.patterns.iter().enumerate() {
+                let mut captures = Vec::new();
+                let found =
+                    matcher.element(&top.pattern, element, &mut captures, &mut |captures| {
+                        matcher.predicates_hold(&top.predicates, captures)
+                    });
+
+                if found {
+                    matches.push(QueryMatch {
+                        pattern: index,
+                        captures: captures
+                            .into_iter()
+                            .map(|(index, element)| self.capture(index, element))
+                            .collect(),
+                    });
+                }
+            }
+        }
+
+        matches
+    }
+
+    fn capture(&self, index: usize, element: Element) -> Capture {
+        let range = element.text_range();
+        Capture {
+            name: self.capture_names[index].clone(),
+            element: match element {
+                NodeOrToken::Node(node) => NodeOrToken::Node(node.clone()),
+                NodeOrToken::Token(token) => NodeOrToken::Token(token.clone()),
+            },
+            range: u32::from(range.start()) as usize..u32::from(range.end()) as usize,
+        }
+    }
+}
+
+type Captures<'a> = Vec<(usize, Element<'a>)>;
+
+struct Matcher<'r, I: ?Sized> {
+    resolver: &'r I,
+}
+
+impl<'r, I> Matcher<'r, I>
+where
+    I: Resolver + ?Sized,
+{
+    // Matches a single element and then calls `next`, backtracking into the
+    // children whenever `next` fails. Results can't be memoized by position,
+    // since whether `next` succeeds depends on the captures made so far.
+    fn element<'a>(
+        &self,
+        pattern: &Pattern,
+        element: Element<'a>,
+        captures: &mut Captures<'a>,
+        next: &mut dyn FnMut(&mut Captures<'a>) -> bool,
+    ) -> bool {
+        let head = match &pattern.head {
+            Head::Kind(kind) => element.kind() == *kind,
+            Head::Text(text) => element.as_token().map_or(false, |token| {
+                token.resolve_text(self.resolver) == text.as_str()
+            }),
+            Head::Any => !element.kind().is_trivia(),
+        };
+
+        let children: Vec<Element> = match element {
+            NodeOrToken::Node(node) => node.children_with_tokens().collect(),
+            NodeOrToken::Token(_) => Vec::new(),
+        };
+
+        let absent = pattern
+            .absent
+            .iter()
+            .all(|&kind| children.iter().all(|child| child.kind() != kind));
+
+        if !head || !absent {
+            return false;
+        }
+
+        let saved = captures.len();
+        captures.extend(pattern.captures.iter().map(|&index| (index, element)));
+        if self.sequence(&pattern.children, &children, 0, captures, next) {
+            return true;
+        }
+
+        captures.truncate(saved);
+        false
+    }
+
+    fn sequence<'a>(
+        &self,
+        patterns: &[Pattern],
+        children: &[Element<'a>],
+        start: usize,
+        captures: &mut Captures<'a>,
+        next: &mut dyn FnMut(&mut Captures<'a>) -> bool,
+    ) -> bool {
+        match patterns.split_first() {
+            Some((first, rest)) => self.repeat(first, rest, children, start, 0, captures, next),
+            None => next(captures),
+        }
+    }
+
+    // Matches `pattern` as often as its quantifier allows, preferring more
+    // repetitions over fewer, and continues with the rest of the sequence.
+    #[allow(clippy::too_many_arguments)]
+    fn repeat<'a>(
+        &self,
+        pattern: &Pattern,
+        rest: &[Pattern],
+        children: &[Element<'a>],
+        start: usize,
+        count: usize,
+        captures: &mut Captures<'a>,
+        next: &mut dyn FnMut(&mut Captures<'a>) -> bool,
+    ) -> bool {
+        let (min, max) = pattern.quantifier.bounds();
+        if count < max {
+            for (i, &child) in children.iter().enumerate().skip(start) {
+                let found = self.element(pattern, child, captures, &mut |captures| {
+                    self.repeat(pattern, rest, children, i + 1, count + 1, captures, next)
+                });
+
+                if found {
+                    return true;
+                }
+            }
+        }
+
+        count >= min && self.sequence(rest, children, start, captures, next)
+    }
+
+    fn predicates_hold(&self, predicates: &[Predicate], captures: &Captures) -> bool {
+        let texts = |index: usize| {
+            captures
+                .iter()
+                .filter(move |(capture, _)| *capture == index)
+                .map(move |&(_, element)| self.text(element))
+        };
+
+        predicates.iter().all(|predicate| match predicate {
+            Predicate::Eq {
+                capture,
+                value,
+                negate,
+            } => {
+                let values: Vec<String> = match value {
+                    Argument::Capture(other) => texts(*other).collect(),
+                    Argument::Text(text) => vec![text.clone()],
+                };
+
+                texts(*capture).all(|text| values.iter().all(|value| (text == *value) != *negate))
+            },
+            Predicate::AnyOf { capture, values } =>
+                texts(*capture).all(|text| values.contains(&text)),
+        })
+    }
+
+    fn text(&self, element: Element) -> String {
+        match element {
+            NodeOrToken::Node(node) => node.resolve_text(self.resolver).to_string(),
+            NodeOrToken::Token(token) => token.resolve_text(self.resolver).to_owned(),
+        }
+    }
+}
+
+struct QueryParser<'q> {
+    source: &'q str,
+    pos: usize,
+    capture_names: Vec<String>,
+    // The captures of the top-level pattern being parsed, which are the only
+    // ones its predicates may refer to.
+    pattern_captures: Vec<usize>,
+}
+
+impl<'q> QueryParser<'q> {
+    fn error(&self, start: usize, message: impl Into<String>) -> QueryError {
+        QueryError {
+            range: start..self.pos.max(start),
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'q str {
+        &self.source[self.pos..]
+    }
+
+    // Skips whitespace and comments and returns the next character.
+    fn peek(&mut self) -> Option<char> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if !trimmed.starts_with(';') {
+                return trimmed.chars().next();
+            }
+
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn at_predicate(&mut self) -> bool {
+        self.peek() == Some('(') && self.rest()[1..].trim_start().starts_with('#')
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(self.pos, format!("expected `{}`", c)))
+        }
+    }
+
+    fn name(&mut self, extra: &[char]) -> &'q str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || extra.contains(&c)))
+            .unwrap_or(rest.len());
+
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn kind(&mut self) -> Result<SyntaxKind, QueryError> {
+        self.peek();
+        let start = self.pos;
+        let name = self.name(&[]);
+        if name.is_empty() {
+            return Err(self.error(start, "expected a kind"));
+        }
+
+        kind_from_name(name).ok_or_else(|| self.error(start, format!("unknown kind `{}`", name)))
+    }
+
+    fn pattern(&mut self, predicates: &mut Vec<Predicate>) -> Result<Pattern, QueryError> {
+        let start = self.pos;
+        let mut pattern = Pattern {
+            head: Head::Any,
+            children: Vec::new(),
+            absent: Vec::new(),
+            quantifier: Quantifier::One,
+            captures: Vec::new(),
+        };
+
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                if !self.eat('_') {
+                    pattern.head = Head::Kind(self.kind()?);
+                }
+
+                loop {
+                    match self.peek() {
+                        Some(')') => break,
+                        Some('!') => {
+                            self.pos += 1;
+                            pattern.absent.push(self.kind()?);
+                        },
+                        Some('(') if self.at_predicate() => predicates.push(self.predicate()?),
+                        Some(_) => pattern.children.push(self.pattern(predicates)?),
+                        None => return Err(self.error(start, "unclosed pattern")),
+                    }
+                }
+
+                self.pos += 1;
+            },
+            Some('"') => pattern.head = Head::Text(self.string()?),
+            Some('_') => self.pos += 1,
+            _ => return Err(self.error(self.pos, "expected a pattern")),
+        }
+
+        pattern.quantifier = match self.peek() {
+            Some('?') => Quantifier::Optional,
+            Some('*') => Quantifier::ZeroOrMore,
+            Some('+') => Quantifier::OneOrMore,
+            _ => Quantifier::One,
+        };
+
+        if pattern.quantifier != Quantifier::One {
+            self.pos += 1;
+        }
+
+        while self.peek() == Some('@') {
+            self.pos += 1;
+            let name = self.capture_name()?;
+            let index = match self.capture_names.iter().position(|known| *known == name) {
+                Some(index) => index,
+                None => {
+                    self.capture_names.push(name.to_owned());
+                    self.capture_names.len() - 1
+                },
+            };
+
+            self.pattern_captures.push(index);
+            pattern.captures.push(index);
+        }
+
+        Ok(pattern)
+    }
+
+    fn capture_name(&mut self) -> Result<&'q str, QueryError> {
+        let start = self.pos;
+        let name = self.name(&['-', '.']);
+        if name.is_empty() {
+            return Err(self.error(start, "expected a capture name"));
+        }
+
+        Ok(name)
+    }
+
+    fn string(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        self.expect('"')?;
+
+        let mut value = String::new();
+        let mut chars = self.rest().chars();
+        loop {
+            let c = match chars.next() {
+                Some(c) => c,
+                None => {
+                    self.pos = self.source.len();
+                    return Err(self.error(start, "unterminated string"));
+                },
+            };
+
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = chars.next().unwrap_or('\\');
+                    self.pos += escaped.len_utf8();
+                    value.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        c => c,
+                    });
+                },
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, QueryError> {
+        let start = self.pos;
+        self.expect('(')?;
+        self.expect('#')?;
+        let name = self.name(&['-', '?', '!']);
+
+        let mut arguments = Vec::new();
+        loop {
+            match self.peek() {
+                Some(')') => break,
+                Some('@') => {
+                    self.pos += 1;
+                    let capture_start = self.pos;
+                    let name = self.capture_name()?;
+                    let index = self
+                        .capture_names
+                        .iter()
+                        .position(|known| known == name)
+                        .filter(|index| self.pattern_captures.contains(index))
+                        .ok_or_else(|| {
+                            self.error(capture_start, format!("unknown capture `{}`", name))
+                        })?;
+
+                    arguments.push(Argument::Capture(index));
+                },
+                Some('"') => arguments.push(Argument::Text(self.string()?)),
+                Some(_) => return Err(self.error(self.pos, "expected a capture or a string")),
+                None => return Err(self.error(start, "unclosed predicate")),
+            }
+        }
+
+        self.pos += 1;
+        match (name, arguments.as_slice()) {
+            ("eq?" | "not-eq?", [Argument::Capture(capture), value]) => Ok(Predicate::Eq {
+                capture: *capture,
+                value: value.clone(),
+                negate: name == "not-eq?",
+            }),
+            ("any-of?", [Argument::Capture(capture), values @ ..]) if !values.is_empty() => {
+                let values = values
+                    .iter()
+                    .map(|value| match value {
+                        Argument::Text(text) => Ok(text.clone()),
+                        Argument::Capture(_) => Err(self.error(start, "expected strings")),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Predicate::AnyOf {
+                    capture: *capture,
+                    values,
+                })
+            },
+            ("eq?" | "not-eq?" | "any-of?", _) =>
+                Err(self.error(start, format!("invalid arguments to `#{}`", name))),
+            _ => Err(self.error(start, format!("unknown predicate `#{}`", name))),
+        }
+    }
+}
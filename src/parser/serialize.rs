@@ -0,0 +1,189 @@
+use std::fmt::{self, Display, Write};
+
+use cstree::{
+    interning::{Interner, Resolver},
+    GreenNodeBuilder,
+    NodeCache,
+    NodeOrToken,
+    TextRange,
+};
+use serde_json::{json, Map, Value};
+
+use super::syntax::{kind_from_name, SyntaxNode};
+
+/// A problem with a tree handed to [`from_json`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeError {
+    pub message: String,
+}
+
+impl TreeError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Converts a tree into JSON. Every element is an object with its `kind`, as
+/// printed by `SyntaxNode::debug`, and its byte `span`. Nodes also have their
+/// `children` and tokens their `text`.
+///
+/// Trivia is included, so the output can be turned back into the same tree
+/// with [`from_json`].
+pub fn to_json<I>(node: &SyntaxNode, resolver: &I) -> String
+where
+    I: Resolver + ?Sized,
+{
+    node_to_json(node, resolver).to_string()
+}
+
+fn node_to_json<I>(node: &SyntaxNode, resolver: &I) -> Value
+where
+    I: Resolver + ?Sized,
+{
+    let children: Vec<Value> = node
+        .children_with_tokens()
+        .map(|element| match element {
+            NodeOrToken::Node(child) => node_to_json(child, resolver),
+            NodeOrToken::Token(token) => json!({
+                "kind": format!("{:?}", token.kind()),
+                "span": span(token.text_range()),
+                "text": token.resolve_text(resolver),
+            }),
+        })
+        .collect();
+
+    json!({
+        "kind": format!("{:?}", node.kind()),
+        "span": span(node.text_range()),
+        "children": children,
+    })
+}
+
+fn span(range: TextRange) -> [u32; 2] {
+    [u32::from(range.start()), u32::from(range.end())]
+}
+
+/// Converts a tree into a compact S-expression, `(Kind children...)` for nodes
+/// and `(Kind "text")` for tokens. Trivia is left out.
+pub fn to_sexpr<I>(node: &SyntaxNode, resolver: &I) -> String
+where
+    I: Resolver + ?Sized,
+{
+    let mut out = String::new();
+    write_sexpr(node, resolver, &mut out);
+    out
+}
+
+fn write_sexpr<I>(node: &SyntaxNode, resolver: &I, out: &mut String)
+where
+    I: Resolver + ?Sized,
+{
+    write!(out, "({:?}", node.kind()).unwrap();
+    for element in node.children_with_tokens() {
+        match element {
+            NodeOrToken::Node(child) => {
+                out.push(' ');
+                write_sexpr(child, resolver, out);
+            },
+            NodeOrToken::Token(token) if token.kind().is_trivia() => {},
+            NodeOrToken::Token(token) => {
+                let text = token.resolve_text(resolver);
+                write!(out, " ({:?} {:?})", token.kind(), text).unwrap();
+            },
+        }
+    }
+
+    out.push(')');
+}
+
+/// Rebuilds a tree from JSON in the format produced by [`to_json`]. Spans may
+/// be left out, but have to match the texts of the tokens if they're given.
+///
+/// The tree isn't checked against the grammar, so it may well be one the
+/// parser would never produce.
+pub fn from_json<I>(cache: &mut NodeCache<'_, I>, json: &str) -> Result<SyntaxNode, TreeError>
+where
+    I: Interner,
+{
+    let value: Value =
+        serde_json::from_str(json).map_err(|error| TreeError::new(error.to_string()))?;
+
+    let mut reader = TreeReader {
+        builder: GreenNodeBuilder::with_cache(cache),
+        offset: 0,
+    };
+
+    let root = object(&value)?;
+    if root.contains_key("text") {
+        return Err(TreeError::new("the root must be a node"));
+    }
+
+    reader.element(root)?;
+    let (green, _) = reader.builder.finish();
+    Ok(SyntaxNode::new_root(green))
+}
+
+struct TreeReader<'cache, 'interner, I> {
+    builder: GreenNodeBuilder<'cache, 'interner, I>,
+    offset: usize,
+}
+
+impl<'cache, 'interner, I> TreeReader<'cache, 'interner, I>
+where
+    I: Interner,
+{
+    fn element(&mut self, element: &Map<String, Value>) -> Result<(), TreeError> {
+        let kind = match element.get("kind").and_then(Value::as_str) {
+            Some(name) => kind_from_name(name)
+                .ok_or_else(|| TreeError::new(format!("unknown kind `{}`", name)))?,
+            None => return Err(TreeError::new("element without a kind")),
+        };
+
+        let start = self.offset;
+        if let Some(text) = element.get("text") {
+            let text = text
+                .as_str()
+                .ok_or_else(|| TreeError::new(format!("text of {:?} isn't a string", kind)))?;
+
+            self.builder.token(kind.into(), text);
+            self.offset += text.len();
+        } else {
+            let children = element
+                .get("children")
+                .and_then(Value::as_array)
+                .ok_or_else(|| {
+                    TreeError::new(format!("{:?} has neither text nor children", kind))
+                })?;
+
+            self.builder.start_node(kind.into());
+            for child in children {
+                self.element(object(child)?)?;
+            }
+
+            self.builder.finish_node();
+        }
+
+        match element.get("span") {
+            None => Ok(()),
+            Some(span) if *span == json!([start, self.offset]) => Ok(()),
+            Some(span) => Err(TreeError::new(format!(
+                "{:?} has span {} but covers {}..{}",
+                kind, span, start, self.offset
+            ))),
+        }
+    }
+}
+
+fn object(value: &Value) -> Result<&Map<String, Value>, TreeError> {
+    value
+        .as_object()
+        .ok_or_else(|| TreeError::new(format!("expected an element, found `{}`", value)))
+}
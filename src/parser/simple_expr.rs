@@ -1,20 +1,24 @@
-use super::{machinery::marker::CompletedMarker, Parser};
+use super::{
+    machinery::{classifiers::token_is_call_args_start, marker::CompletedMarker},
+    Parser,
+};
 use crate::T;
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
+    // Parses a prefix expression, a name or parenthesised expression followed
+    // by any number of calls, indexes and field accesses. Function statement
+    // names can't be parenthesised or contain calls.
     pub(super) fn r_simple_expr(&mut self, allow_call: bool) -> Option<CompletedMarker> {
-        if self.at() == T!['('] {
-            let marker = self.start();
-            self.r_expr();
-            return Some(marker.complete(self, T![simple_expr]));
-        }
-
-        let mut lhs = self.r_ident()?;
+        let mut lhs = if self.at() == T!['('] && allow_call {
+            self.r_paren()?
+        } else {
+            self.r_ident()?
+        };
 
         loop {
             let t = self.at();
 
-            if t == T!['('] && allow_call {
+            if token_is_call_args_start(t) && allow_call {
                 let n = lhs.precede(self);
                 let _rhs = self.r_func_call_args()?;
                 lhs = n.complete(self, T![func_call]);
@@ -30,14 +34,24 @@ impl<'cache, 'source> Parser<'cache, 'source> {
                 continue;
             }
 
-            if t == T![.] || t == T![:] {
-                let n = lhs.precede(self);
-                self.expect(t);
-                let _rhs = self.r_ident();
-                lhs = n.complete(self, T![bin_op]);
+            if t == T![.] {
+                lhs = self.r_field(lhs);
                 continue;
             }
 
+            if t == T![:] && allow_call {
+                lhs = self.r_method_call(lhs)?;
+                continue;
+            }
+
+            // A method name in a function statement ends the name.
+            if t == T![:] {
+                let n = lhs.precede(self);
+                self.expect(T![:]);
+                self.expect(T![ident]);
+                lhs = n.complete(self, T![method_name]);
+            }
+
             break;
         }
 
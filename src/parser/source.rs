@@ -0,0 +1,92 @@
+use std::{
+    fmt::{self, Debug, Display},
+    lazy::SyncOnceCell,
+};
+
+use super::line_index::LineIndex;
+
+/// Identifies a file within a [`SourceDb`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+impl FileId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Debug for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileId({})", self.0)
+    }
+}
+
+struct SourceFile {
+    name: String,
+    text: String,
+    // Copies the text line by line, so it's only built once a diagnostic in
+    // the file is rendered.
+    source: SyncOnceCell<ariadne::Source>,
+    line_index: LineIndex,
+}
+
+/// Holds the names and contents of all files in a project, so that diagnostics
+/// produced while parsing any of them can be rendered together.
+///
+/// A reference to the database implements [`ariadne::Cache`], diagnostics can
+/// be printed with `diagnostic.eprint(&db)`.
+#[derive(Default)]
+pub struct SourceDb {
+    files: Vec<SourceFile>,
+}
+
+impl SourceDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> FileId {
+        let text = text.into();
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile {
+            name: name.into(),
+            source: SyncOnceCell::new(),
+            line_index: LineIndex::new(&text),
+            text,
+        });
+
+        id
+    }
+
+    pub fn name(&self, file: FileId) -> &str {
+        &self.files[file.index()].name
+    }
+
+    pub fn text(&self, file: FileId) -> &str {
+        &self.files[file.index()].text
+    }
+
+    pub fn line_index(&self, file: FileId) -> &LineIndex {
+        &self.files[file.index()].line_index
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = FileId> {
+        (0..self.files.len() as u32).map(FileId)
+    }
+}
+
+impl ariadne::Cache<FileId> for &SourceDb {
+    fn fetch(&mut self, id: &FileId) -> Result<&ariadne::Source, Box<dyn Debug + '_>> {
+        match self.files.get(id.index()) {
+            Some(file) => Ok(file
+                .source
+                .get_or_init(|| ariadne::Source::from(&file.text))),
+            None => Err(Box::new(*id)),
+        }
+    }
+
+    fn display<'a>(&self, id: &'a FileId) -> Option<Box<dyn Display + 'a>> {
+        let file = self.files.get(id.index())?;
+        Some(Box::new(file.name.clone()))
+    }
+}
@@ -1,10 +1,11 @@
 use super::{
+    diagnostic::{Code, Diagnostic},
     machinery::{kind::SyntaxKind, marker::CompletedMarker},
     Parser,
 };
 use crate::T;
 
-const STATEMENT_RECOVERY: &[SyntaxKind] = &[
+pub(super) const STATEMENT_RECOVERY: &[SyntaxKind] = &[
     T![do],
     T![while],
     T![repeat],
@@ -12,11 +13,13 @@ const STATEMENT_RECOVERY: &[SyntaxKind] = &[
     T![for],
     T![return],
     T![break],
+    T![goto],
+    T![::],
     T![function],
     T![local],
 ];
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_stmt(&mut self) -> Option<CompletedMarker> {
         match self.at() {
             T![do] => self.r_do(),
@@ -26,29 +29,50 @@ impl<'cache, 'source> Parser<'cache, 'source> {
             T![for] => self.r_for(),
             T![return] => self.r_return(),
             T![break] => self.r_break(),
+            T![goto] => self.r_goto(),
+            T![::] => self.r_label(),
             T![function] => self.r_func(false),
             T![local] => self.r_decl(),
             T![ident] | T!['('] => self.r_maybe_assign(),
             T![;] => self.r_semicolon(),
+            T![invalid] => self.r_invalid(),
             T![eof] => None,
             _ => {
-                let span = self.error_eat_until(STATEMENT_RECOVERY);
-                let source = self.source(span);
-                let error = self
-                    .new_error()
-                    .with_message("expected a statement")
-                    .with_label(
-                        self.new_label()
-                            .with_message(format!("expected a statement but got \"{}\"", source,)),
-                    )
-                    .finish();
+                let span = self.span();
+                let error = Diagnostic::error(
+                    Code::ExpectedStatement,
+                    "expected a statement",
+                    self.new_label(format!(
+                        "expected a statement but got \"{}\"",
+                        self.source(span)
+                    )),
+                );
 
-                self.report(error);
+                self.syntax_error(error);
+                self.error_eat_until(STATEMENT_RECOVERY);
                 None
             },
         }
     }
 
+    // Reports the current token as unexpected and skips ahead to the next token
+    // the enclosing constructs can resume at.
+    pub(super) fn error_recover(&mut self, expected: &[SyntaxKind]) {
+        let expected: Vec<String> = expected.iter().map(ToString::to_string).collect();
+        let error = Diagnostic::error(
+            Code::UnexpectedToken,
+            "unexpected token",
+            self.new_label(format!(
+                "expected one of {} but found {}",
+                expected.join(", "),
+                self.at()
+            )),
+        );
+
+        self.syntax_error(error);
+        self.error_eat_until(STATEMENT_RECOVERY);
+    }
+
     fn r_semicolon(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T![;]);
@@ -1,4 +1,16 @@
-use crate::{parser::machinery::kind::SyntaxKind, T};
+use std::lazy::SyncLazy;
+
+use cstree::interning::Resolver;
+use fxhash::FxBuildHasher;
+use hashbrown::HashMap;
+
+use crate::{
+    parser::{
+        literal::{decode_number, decode_string, Number},
+        machinery::kind::SyntaxKind,
+    },
+    T,
+};
 
 impl From<SyntaxKind> for cstree::SyntaxKind {
     fn from(token: SyntaxKind) -> Self {
@@ -22,37 +34,637 @@ impl cstree::Language for Lang {
     }
 }
 
+// The kinds that can appear in a tree, by the name their `Debug`
+// implementation prints.
+static KINDS: SyncLazy<HashMap<String, SyntaxKind, FxBuildHasher>> = SyncLazy::new(|| {
+    (0..T![__LAST] as u16)
+        .map(|raw| <Lang as cstree::Language>::kind_from_raw(cstree::SyntaxKind(raw)))
+        .filter(|kind| !matches!(kind, T![tombstone] | T![eof]))
+        .map(|kind| (format!("{:?}", kind), kind))
+        .collect()
+});
+
+/// Looks up a kind by the name its `Debug` implementation prints, which is
+/// also the name used by `SyntaxNode::debug`. Kinds that never appear in a
+/// tree aren't found.
+pub(crate) fn kind_from_name(name: &str) -> Option<SyntaxKind> {
+    KINDS.get(name).copied()
+}
+
 pub type SyntaxNode = cstree::SyntaxNode<Lang>;
 pub type SyntaxToken = cstree::SyntaxToken<Lang>;
 pub type SyntaxElement = cstree::NodeOrToken<SyntaxNode, SyntaxToken>;
 
+/// A typed view over a [`SyntaxNode`] of a specific kind.
+pub trait AstNode: Sized {
+    fn can_cast(kind: SyntaxKind) -> bool;
+
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+}
+
 macro_rules! ast_node {
     ($ast:ident, $kind:expr) => {
-        #[derive(PartialEq, Eq, Hash)]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
         #[repr(transparent)]
-        struct $ast(SyntaxNode);
-        impl $ast {
-            #[allow(unused)]
+        pub struct $ast(SyntaxNode);
+
+        impl AstNode for $ast {
+            fn can_cast(kind: SyntaxKind) -> bool {
+                kind == $kind
+            }
+
             fn cast(node: SyntaxNode) -> Option<Self> {
-                if node.kind() == $kind {
+                if Self::can_cast(node.kind()) {
                     Some(Self(node))
                 } else {
                     None
                 }
             }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+macro_rules! ast_enum {
+    ($ast:ident { $($variant:ident($node:ty)),* $(,)? }) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $ast {
+            $($variant($node),)*
+        }
+
+        impl AstNode for $ast {
+            fn can_cast(kind: SyntaxKind) -> bool {
+                $(<$node>::can_cast(kind))||*
+            }
+
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                $(
+                    if <$node>::can_cast(node.kind()) {
+                        return <$node>::cast(node).map(Self::$variant);
+                    }
+                )*
+
+                None
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                match self {
+                    $(Self::$variant(node) => node.syntax(),)*
+                }
+            }
         }
     };
 }
 
 ast_node!(Root, T![root]);
+ast_node!(StmtList, T![stmt_list]);
+ast_node!(BreakStmt, T![break_stmt]);
+ast_node!(ReturnStmt, T![return_stmt]);
+ast_node!(BlockStmt, T![block_stmt]);
+ast_node!(WhileStmt, T![while_stmt]);
+ast_node!(RepeatStmt, T![repeat_stmt]);
+ast_node!(IfStmt, T![if_stmt]);
+ast_node!(ElseChain, T![else_chain]);
+ast_node!(ForNumStmt, T![for_num_stmt]);
+ast_node!(ForGenStmt, T![for_gen_stmt]);
+ast_node!(FuncStmt, T![func_stmt]);
 ast_node!(DeclStmt, T![decl_stmt]);
 ast_node!(DeclTarget, T![decl_target]);
-ast_node!(LiteralExpr, T![literal_expr]);
+ast_node!(Attrib, T![attrib]);
 ast_node!(AssignStmt, T![assign_stmt]);
+ast_node!(GotoStmt, T![goto_stmt]);
+ast_node!(LabelStmt, T![label_stmt]);
+ast_node!(EmptyStmt, T![;]);
 ast_node!(Ident, T![ident]);
+ast_node!(LiteralExpr, T![literal_expr]);
+ast_node!(VarArgExpr, T![vararg_expr]);
+ast_node!(ParenExpr, T![expr]);
 ast_node!(PrefixOp, T![prefix_op]);
 ast_node!(BinaryOp, T![bin_op]);
-ast_node!(ExprList, T![expr_list]);
 ast_node!(FuncCall, T![func_call]);
-ast_node!(Func, T![function]);
-ast_node!(StmtList, T![stmt_list]);
+ast_node!(MethodCallExpr, T![method_call_expr]);
+ast_node!(FieldExpr, T![field_expr]);
+ast_node!(Index, T![index]);
+ast_node!(FuncExpr, T![func_expr]);
+ast_node!(TableExpr, T![table_expr]);
+ast_node!(ExprList, T![expr_list]);
+ast_node!(FuncArgs, T![func_args]);
+ast_node!(StringArg, T![string_arg]);
+ast_node!(TableArg, T![table_arg]);
+ast_node!(MethodName, T![method_name]);
+ast_node!(TableArrayElem, T![table_array_elem]);
+ast_node!(TableMapElem, T![table_map_elem]);
+ast_node!(TableGenericElem, T![table_generic_elem]);
+
+ast_enum!(Stmt {
+    Break(BreakStmt),
+    Return(ReturnStmt),
+    Block(BlockStmt),
+    While(WhileStmt),
+    Repeat(RepeatStmt),
+    If(IfStmt),
+    ForNum(ForNumStmt),
+    ForGen(ForGenStmt),
+    Func(FuncStmt),
+    Decl(DeclStmt),
+    Assign(AssignStmt),
+    Goto(GotoStmt),
+    Label(LabelStmt),
+    Empty(EmptyStmt),
+    Expr(Expr),
+});
+
+ast_enum!(Expr {
+    Ident(Ident),
+    Literal(LiteralExpr),
+    VarArg(VarArgExpr),
+    Paren(ParenExpr),
+    PrefixOp(PrefixOp),
+    BinaryOp(BinaryOp),
+    FuncCall(FuncCall),
+    MethodCall(MethodCallExpr),
+    Field(FieldExpr),
+    Index(Index),
+    Func(FuncExpr),
+    Table(TableExpr),
+});
+
+ast_enum!(CallArgs {
+    Paren(FuncArgs),
+    String(StringArg),
+    Table(TableArg),
+});
+
+ast_enum!(TableElem {
+    Array(TableArrayElem),
+    Map(TableMapElem),
+    Generic(TableGenericElem),
+});
+
+ast_enum!(FuncName {
+    Expr(Expr),
+    Method(MethodName),
+});
+
+fn child<N: AstNode>(parent: &SyntaxNode) -> Option<N> {
+    parent.children().cloned().find_map(N::cast)
+}
+
+fn children<N: AstNode>(parent: &SyntaxNode) -> impl Iterator<Item = N> + '_ {
+    parent.children().cloned().filter_map(N::cast)
+}
+
+fn tokens(parent: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> + '_ {
+    parent
+        .children_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia())
+        .cloned()
+}
+
+fn token(parent: &SyntaxNode, kind: SyntaxKind) -> Option<SyntaxToken> {
+    tokens(parent).find(|token| token.kind() == kind)
+}
+
+// Operands are found relative to the tokens around them rather than by their
+// position among the children, so that one which failed to parse, leaving a
+// missing or invalid node in its place, doesn't shift the next one forward.
+fn expr_before(parent: &SyntaxNode, kind: SyntaxKind) -> Option<Expr> {
+    parent
+        .children_with_tokens()
+        .filter(|element| !element.kind().is_trivia())
+        .take_while(|element| element.kind() != kind)
+        .last()?
+        .into_node()
+        .cloned()
+        .and_then(Expr::cast)
+}
+
+// The expression directly following the `nth` token of `kind`.
+fn expr_after(parent: &SyntaxNode, kind: SyntaxKind, nth: usize) -> Option<Expr> {
+    let mut elements = parent
+        .children_with_tokens()
+        .filter(|element| !element.kind().is_trivia());
+
+    elements
+        .by_ref()
+        .filter(|element| element.kind() == kind)
+        .nth(nth)?;
+    elements.next()?.into_node().cloned().and_then(Expr::cast)
+}
+
+// Expression lists of a single expression aren't wrapped in an `ExprList`.
+fn expr_list(parent: &SyntaxNode) -> Vec<Expr> {
+    match child::<ExprList>(parent) {
+        Some(list) => list.exprs().collect(),
+        None => children(parent).collect(),
+    }
+}
+
+impl Root {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt> + '_ {
+        children(&self.0)
+    }
+}
+
+impl StmtList {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt> + '_ {
+        children(&self.0)
+    }
+}
+
+impl ReturnStmt {
+    pub fn exprs(&self) -> Vec<Expr> {
+        expr_list(&self.0)
+    }
+}
+
+impl BlockStmt {
+    pub fn block(&self) -> Option<StmtList> {
+        child(&self.0)
+    }
+}
+
+impl WhileStmt {
+    pub fn condition(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<BlockStmt> {
+        child(&self.0)
+    }
+}
+
+impl RepeatStmt {
+    pub fn block(&self) -> Option<StmtList> {
+        child(&self.0)
+    }
+
+    pub fn condition(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl IfStmt {
+    pub fn condition(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn block(&self) -> Option<StmtList> {
+        child(&self.0)
+    }
+
+    pub fn else_chain(&self) -> Option<ElseChain> {
+        child(&self.0)
+    }
+}
+
+impl ElseChain {
+    /// The `elseif` branch continuing the chain, if any.
+    pub fn else_if(&self) -> Option<IfStmt> {
+        child(&self.0)
+    }
+
+    /// The block of the final `else` branch, if any.
+    pub fn block(&self) -> Option<StmtList> {
+        child(&self.0)
+    }
+}
+
+impl ForNumStmt {
+    pub fn counter(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+
+    pub fn start(&self) -> Option<Expr> {
+        expr_after(&self.0, T![=], 0)
+    }
+
+    pub fn end(&self) -> Option<Expr> {
+        expr_after(&self.0, T![,], 0)
+    }
+
+    pub fn step(&self) -> Option<Expr> {
+        expr_after(&self.0, T![,], 1)
+    }
+
+    pub fn body(&self) -> Option<BlockStmt> {
+        child(&self.0)
+    }
+}
+
+impl ForGenStmt {
+    pub fn names(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        tokens(&self.0).filter(|token| token.kind() == T![ident])
+    }
+
+    pub fn exprs(&self) -> Vec<Expr> {
+        expr_list(&self.0)
+    }
+
+    pub fn body(&self) -> Option<BlockStmt> {
+        child(&self.0)
+    }
+}
+
+impl FuncStmt {
+    pub fn name(&self) -> Option<FuncName> {
+        child(&self.0)
+    }
+
+    pub fn params(&self) -> Option<FuncArgs> {
+        child(&self.0)
+    }
+
+    pub fn block(&self) -> Option<StmtList> {
+        child(&self.0)
+    }
+}
+
+impl DeclStmt {
+    pub fn targets(&self) -> impl Iterator<Item = DeclTarget> + '_ {
+        children(&self.0)
+    }
+
+    pub fn values(&self) -> Vec<Expr> {
+        expr_list(&self.0)
+    }
+
+    /// The function of a `local function` declaration.
+    pub fn function(&self) -> Option<FuncStmt> {
+        child(&self.0)
+    }
+}
+
+impl DeclTarget {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+
+    pub fn attrib(&self) -> Option<Attrib> {
+        child(&self.0)
+    }
+}
+
+impl Attrib {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+}
+
+impl AssignStmt {
+    pub fn targets(&self) -> impl Iterator<Item = Expr> + '_ {
+        self.0
+            .children_with_tokens()
+            .take_while(|element| element.kind() != T![=])
+            .filter_map(|element| element.into_node())
+            .cloned()
+            .filter_map(Expr::cast)
+    }
+
+    pub fn values(&self) -> Vec<Expr> {
+        let mut values = self
+            .0
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != T![=])
+            .filter_map(|element| element.into_node())
+            .cloned();
+
+        match values.next() {
+            Some(node) if node.kind() == T![expr_list] => children(&node).collect(),
+            first => first
+                .into_iter()
+                .chain(values)
+                .filter_map(Expr::cast)
+                .collect(),
+        }
+    }
+}
+
+impl GotoStmt {
+    pub fn label(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+}
+
+impl LabelStmt {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+}
+
+impl Ident {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+}
+
+impl LiteralExpr {
+    pub fn token(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+
+    /// The bytes a string literal evaluates to, if this is a valid one.
+    pub fn string_value<I>(&self, resolver: &I) -> Option<Vec<u8>>
+    where
+        I: Resolver + ?Sized,
+    {
+        let token = self.token()?;
+        if !matches!(token.kind(), T![string] | T![long_string]) {
+            return None;
+        }
+
+        decode_string(token.resolve_text(resolver)).ok()
+    }
+
+    /// The value of a numeric literal.
+    pub fn number_value<I>(&self, resolver: &I) -> Option<Number>
+    where
+        I: Resolver + ?Sized,
+    {
+        let token = self.token()?;
+        if !matches!(
+            token.kind(),
+            T![int] | T![hex_int] | T![float] | T![hex_float]
+        ) {
+            return None;
+        }
+
+        decode_number(token.resolve_text(resolver))
+    }
+}
+
+impl ParenExpr {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl PrefixOp {
+    pub fn op(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl BinaryOp {
+    pub fn lhs(&self) -> Option<Expr> {
+        expr_before(&self.0, self.op()?.kind())
+    }
+
+    pub fn op(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+
+    pub fn rhs(&self) -> Option<Expr> {
+        expr_after(&self.0, self.op()?.kind(), 0)
+    }
+}
+
+impl FuncCall {
+    pub fn callee(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn args(&self) -> Option<CallArgs> {
+        child(&self.0)
+    }
+}
+
+impl MethodCallExpr {
+    pub fn receiver(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn method(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+
+    pub fn args(&self) -> Option<CallArgs> {
+        child(&self.0)
+    }
+}
+
+impl FieldExpr {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn field(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+}
+
+impl Index {
+    pub fn expr(&self) -> Option<Expr> {
+        expr_before(&self.0, T!['['])
+    }
+
+    pub fn index(&self) -> Option<Expr> {
+        expr_after(&self.0, T!['['], 0)
+    }
+}
+
+impl FuncExpr {
+    pub fn params(&self) -> Option<FuncArgs> {
+        child(&self.0)
+    }
+
+    pub fn block(&self) -> Option<StmtList> {
+        child(&self.0)
+    }
+}
+
+impl TableExpr {
+    pub fn elems(&self) -> impl Iterator<Item = TableElem> + '_ {
+        children(&self.0)
+    }
+}
+
+impl ExprList {
+    pub fn exprs(&self) -> impl Iterator<Item = Expr> + '_ {
+        children(&self.0)
+    }
+}
+
+impl FuncArgs {
+    /// The arguments passed to a call.
+    pub fn exprs(&self) -> impl Iterator<Item = Expr> + '_ {
+        children(&self.0)
+    }
+
+    /// The parameter names of a function definition.
+    pub fn params(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        tokens(&self.0).filter(|token| token.kind() == T![ident])
+    }
+
+    pub fn is_vararg(&self) -> bool {
+        token(&self.0, T![...]).is_some()
+    }
+}
+
+impl StringArg {
+    pub fn literal(&self) -> Option<LiteralExpr> {
+        child(&self.0)
+    }
+}
+
+impl TableArg {
+    pub fn table(&self) -> Option<TableExpr> {
+        child(&self.0)
+    }
+}
+
+impl CallArgs {
+    pub fn exprs(&self) -> Vec<Expr> {
+        match self {
+            Self::Paren(args) => args.exprs().collect(),
+            Self::String(arg) => arg.literal().map(Expr::Literal).into_iter().collect(),
+            Self::Table(arg) => arg.table().map(Expr::Table).into_iter().collect(),
+        }
+    }
+}
+
+impl MethodName {
+    pub fn receiver(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn method(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+}
+
+impl TableArrayElem {
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl TableMapElem {
+    pub fn key(&self) -> Option<SyntaxToken> {
+        token(&self.0, T![ident])
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl TableGenericElem {
+    pub fn key(&self) -> Option<Expr> {
+        expr_after(&self.0, T!['['], 0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        expr_after(&self.0, T![=], 0)
+    }
+}
@@ -1,34 +1,33 @@
 use super::{
+    diagnostic::{Code, Diagnostic},
     machinery::{classifiers::token_is_expr_start, marker::CompletedMarker},
+    stmt::STATEMENT_RECOVERY,
     Parser,
 };
 use crate::T;
 
-impl<'cache, 'source> Parser<'cache, 'source> {
+impl<'source> Parser<'source> {
     pub(super) fn r_table(&mut self) -> Option<CompletedMarker> {
         let marker = self.start();
         self.expect(T!['{']);
+        self.enter_recovery(&[T![,], T![;], T!['}']]);
 
-        loop {
-            match self.at() {
-                T!['}'] => {
-                    self.expect(T!['}']);
-                    break;
-                },
-                _ => {
-                    self.r_table_elem();
-                },
+        while self.at() != T!['}'] {
+            self.r_table_elem();
+            if !self.at_recovery() {
+                self.error_recover(&[T![,], T![;], T!['}']]);
             }
 
             let t = self.at();
-            if t == T![,] || t == T![;] {
-                self.expect(t);
-            } else {
-                self.expect(T!['}']);
+            if t != T![,] && t != T![;] {
                 break;
             }
+
+            self.expect(t);
         }
 
+        self.exit_recovery();
+        self.expect(T!['}']);
         Some(marker.complete(self, T![table_expr]))
     }
 
@@ -37,7 +36,17 @@ impl<'cache, 'source> Parser<'cache, 'source> {
             T![ident] if self.peek() == T![=] => self.r_table_elem_map(),
             T!['['] => self.r_table_elem_generic(),
             t if token_is_expr_start(t) => self.r_table_elem_array(),
-            _ => unreachable!(),
+            _ => {
+                let error = Diagnostic::error(
+                    Code::ExpectedTableElement,
+                    "expected a table element",
+                    self.new_label(format!("expected a table element but found {}", self.at())),
+                );
+
+                self.syntax_error(error);
+                self.error_eat_until(STATEMENT_RECOVERY);
+                None
+            },
         }
     }
 
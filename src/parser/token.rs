@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+use logos::{Lexer, Logos};
+
+use super::{
+    literal::validate_string,
+    machinery::kind::{lex_header, SyntaxKind},
+};
+use crate::T;
+
+/// A token as produced by the lexer, trivia included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'source> {
+    pub kind: SyntaxKind,
+    pub text: &'source str,
+    pub range: Range<usize>,
+    /// What's wrong with the token, if the lexer already knows. Invalid tokens
+    /// always have an error, strings have one if they contain an invalid
+    /// escape sequence.
+    pub error: Option<&'static str>,
+}
+
+/// Lexes the source into tokens without parsing it. Together the tokens cover
+/// the whole source.
+pub fn tokenize(source: &str) -> Tokens<'_> {
+    Tokens { raw: lex(source) }
+}
+
+/// The iterator returned by [`tokenize`].
+pub struct Tokens<'source> {
+    raw: RawTokens<'source>,
+}
+
+impl<'source> Iterator for Tokens<'source> {
+    type Item = Token<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut token = self.raw.next()?;
+        if token.kind == T![string] {
+            token.error = validate_string(token.text)
+                .err()
+                .map(|errors| errors[0].message);
+        }
+
+        Some(token)
+    }
+}
+
+// Lexes the source like `tokenize`, but leaves the escapes in strings
+// unchecked. The parser reports those itself, with a span for each one.
+pub(super) fn lex(source: &str) -> RawTokens<'_> {
+    let header = lex_header(source);
+    let mut lexer = SyntaxKind::lexer(source);
+    if let Some((_, range)) = header.last() {
+        lexer.bump(range.end);
+    }
+
+    RawTokens {
+        header: header.into_iter(),
+        lexer,
+    }
+}
+
+pub(super) struct RawTokens<'source> {
+    header: std::vec::IntoIter<(SyntaxKind, Range<usize>)>,
+    lexer: Lexer<'source, SyntaxKind>,
+}
+
+impl<'source> Iterator for RawTokens<'source> {
+    type Item = Token<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((kind, range)) = self.header.next() {
+            let text = &self.lexer.source()[range.clone()];
+            return Some(Token {
+                kind,
+                text,
+                range,
+                error: None,
+            });
+        }
+
+        let kind = self.lexer.next()?;
+        let range = self.lexer.span();
+        let text = self.lexer.slice();
+        let error = match kind {
+            T![invalid] => Some(
+                self.lexer
+                    .extras
+                    .iter()
+                    .rev()
+                    .find(|error| error.range == range)
+                    .map_or("unexpected character", |error| error.message),
+            ),
+            _ => None,
+        };
+
+        Some(Token {
+            kind,
+            text,
+            range,
+            error,
+        })
+    }
+}
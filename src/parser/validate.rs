@@ -0,0 +1,374 @@
+use cstree::{interning::Resolver, TextRange};
+
+use super::{
+    diagnostic::{Code, Diagnostic, Label, Suggestion},
+    machinery::span::Span,
+    source::FileId,
+    syntax::{AstNode, Attrib, DeclStmt, Expr, FuncArgs, FuncName, SyntaxNode},
+};
+use crate::T;
+
+/// The codes of the reports [`validate`] produces.
+pub(super) const CODES: &[Code] = &[
+    Code::StatementAfterReturn,
+    Code::AssignToConst,
+    Code::BreakOutsideLoop,
+    Code::VarArgOutsideVarArgFunction,
+    Code::MultipleClose,
+];
+
+/// Checks a tree for errors that the grammar can't express but the reference
+/// implementation still rejects at compile time.
+pub(super) fn validate<I>(root: &SyntaxNode, resolver: &I, file: FileId) -> Vec<Diagnostic>
+where
+    I: Resolver + ?Sized,
+{
+    let mut validator = Validator {
+        resolver,
+        file,
+        scopes: Vec::new(),
+        // The file is the body of a function taking `...`.
+        functions: vec![Function {
+            vararg: true,
+            loops: 0,
+        }],
+        reports: Vec::new(),
+    };
+
+    validator.scope(root);
+    validator.reports
+}
+
+struct Local<'i> {
+    name: &'i str,
+    // The attribute that makes the local read-only, including the brackets.
+    constant: Option<Span>,
+}
+
+struct Function {
+    vararg: bool,
+    loops: usize,
+}
+
+struct Validator<'i, I: ?Sized> {
+    resolver: &'i I,
+    file: FileId,
+    scopes: Vec<Vec<Local<'i>>>,
+    functions: Vec<Function>,
+    reports: Vec<Diagnostic>,
+}
+
+impl<'i, I> Validator<'i, I>
+where
+    I: Resolver + ?Sized,
+{
+    fn node(&mut self, node: &SyntaxNode) {
+        match node.kind() {
+            T![stmt_list] => self.scope(node),
+            T![while_stmt] | T![repeat_stmt] => self.r_loop(node),
+            T![for_num_stmt] | T![for_gen_stmt] => self.r_for(node),
+            T![func_stmt] | T![func_expr] => self.function(node),
+            T![decl_stmt] => self.decl(node),
+            T![assign_stmt] => {
+                let targets = node
+                    .children_with_tokens()
+                    .take_while(|element| element.kind() != T![=])
+                    .filter_map(|element| element.into_node())
+                    .cloned()
+                    .filter_map(Expr::cast);
+
+                for target in targets {
+                    self.assign(&target);
+                }
+
+                self.children(node);
+            },
+            T![break_stmt] => self.r_break(node),
+            T![vararg_expr] => self.vararg(node),
+            _ => self.children(node),
+        }
+    }
+
+    fn children(&mut self, node: &SyntaxNode) {
+        for child in node.children() {
+            self.node(child);
+        }
+    }
+
+    fn scope(&mut self, node: &SyntaxNode) {
+        self.scopes.push(Vec::new());
+        self.block(node);
+        self.scopes.pop();
+    }
+
+    // Walks the statements of a block without opening a new scope. Only a
+    // single semicolon may follow a return statement.
+    fn block(&mut self, node: &SyntaxNode) {
+        let statements: Vec<&SyntaxNode> = node
+            .children()
+            .filter(|child| !matches!(child.kind(), T![invalid] | T![missing]))
+            .collect();
+
+        let ret = statements
+            .iter()
+            .position(|statement| statement.kind() == T![return_stmt]);
+
+        if let Some(ret) = ret {
+            let mut rest = statements[ret + 1..].iter();
+            let next = match rest.next() {
+                Some(statement) if statement.kind() == T![;] => rest.next(),
+                next => next,
+            };
+
+            if let Some(next) = next {
+                let span = self.span(next.text_range());
+                let ret = self.span(statements[ret].text_range());
+                let error = Diagnostic::error(
+                    Code::StatementAfterReturn,
+                    "statement after return",
+                    Label::new(span, "a return statement has to be the last in its block"),
+                )
+                .with_label(Label::new(ret, "the block returns here"))
+                .with_note("to return early, wrap the return statement in `do ... end`");
+
+                self.reports.push(error);
+            }
+        }
+
+        for statement in statements {
+            self.node(statement);
+        }
+    }
+
+    fn r_loop(&mut self, node: &SyntaxNode) {
+        self.current().loops += 1;
+
+        // The condition of a repeat loop can see the locals of its block.
+        if node.kind() == T![repeat_stmt] {
+            self.scopes.push(Vec::new());
+            for child in node.children() {
+                if child.kind() == T![stmt_list] {
+                    self.block(child);
+                } else {
+                    self.node(child);
+                }
+            }
+
+            self.scopes.pop();
+        } else {
+            self.children(node);
+        }
+
+        self.current().loops -= 1;
+    }
+
+    // The control variables are only visible in the body of the loop, the
+    // expressions are evaluated outside of it.
+    fn r_for(&mut self, node: &SyntaxNode) {
+        let (body, exprs): (Vec<&SyntaxNode>, Vec<&SyntaxNode>) = node
+            .children()
+            .partition(|child| child.kind() == T![block_stmt]);
+
+        for expr in exprs {
+            self.node(expr);
+        }
+
+        let names = node
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .filter(|token| token.kind() == T![ident])
+            .map(|token| Local {
+                name: token.resolve_text(self.resolver),
+                constant: None,
+            })
+            .collect();
+
+        self.current().loops += 1;
+        self.scopes.push(names);
+        for child in body {
+            self.node(child);
+        }
+
+        self.scopes.pop();
+        self.current().loops -= 1;
+    }
+
+    // Breaks and loops don't cross function boundaries, so every function
+    // starts counting loops from zero.
+    fn function(&mut self, node: &SyntaxNode) {
+        let is_local = node.parent().map(SyntaxNode::kind) == Some(T![decl_stmt]);
+        if !is_local {
+            if let Some(FuncName::Expr(name)) = node.children().cloned().find_map(FuncName::cast) {
+                self.assign(&name);
+            }
+        }
+
+        let params = node.children().cloned().find_map(FuncArgs::cast);
+        let vararg = params.as_ref().map_or(false, FuncArgs::is_vararg);
+        let params = params
+            .iter()
+            .flat_map(FuncArgs::params)
+            .map(|param| Local {
+                name: param.resolve_text(self.resolver),
+                constant: None,
+            })
+            .collect();
+
+        self.functions.push(Function { vararg, loops: 0 });
+        self.scopes.push(params);
+        for child in node
+            .children()
+            .filter(|child| child.kind() == T![stmt_list])
+        {
+            self.block(child);
+        }
+
+        self.scopes.pop();
+        self.functions.pop();
+    }
+
+    // The values are evaluated before the names come into scope, except for a
+    // local function, which can refer to itself.
+    fn decl(&mut self, node: &SyntaxNode) {
+        let decl = DeclStmt::cast(node.clone()).unwrap();
+        if let Some(function) = decl.function() {
+            if let Some(FuncName::Expr(Expr::Ident(name))) = function.name() {
+                if let Some(name) = name.name() {
+                    self.scopes.last_mut().unwrap().push(Local {
+                        name: name.resolve_text(self.resolver),
+                        constant: None,
+                    });
+                }
+            }
+
+            self.function(function.syntax());
+            return;
+        }
+
+        for child in node
+            .children()
+            .filter(|child| child.kind() != T![decl_target])
+        {
+            self.node(child);
+        }
+
+        let mut close = None;
+        for target in decl.targets() {
+            let mut constant = None;
+            let attrib = target.attrib();
+            let name = attrib.as_ref().and_then(Attrib::name);
+            if let (Some(attrib), Some(name)) = (attrib, name) {
+                let attrib = self.span(attrib.syntax().text_range());
+                let span = self.span(name.text_range());
+                match name.resolve_text(self.resolver) {
+                    "const" => constant = Some(attrib),
+                    "close" => {
+                        constant = Some(attrib);
+                        match close {
+                            Some(previous) => self.report_multiple_close(span, previous),
+                            None => close = Some(span),
+                        }
+                    },
+                    _ => {},
+                }
+            }
+
+            if let Some(name) = target.name() {
+                self.scopes.last_mut().unwrap().push(Local {
+                    name: name.resolve_text(self.resolver),
+                    constant,
+                });
+            }
+        }
+    }
+
+    // Reports assignments to `<const>` and `<close>` locals, both of which are
+    // read-only.
+    fn assign(&mut self, target: &Expr) {
+        let name = match target {
+            Expr::Ident(ident) => match ident.name() {
+                Some(name) => name,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let text = name.resolve_text(self.resolver);
+        let constant = self
+            .scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|local| local.name == text)
+            .and_then(|local| local.constant);
+
+        if let Some(attrib) = constant {
+            let span = self.span(name.text_range());
+            let error = Diagnostic::error(
+                Code::AssignToConst,
+                format!("attempt to assign to const variable \"{}\"", text),
+                Label::new(span, "cannot assign to a constant"),
+            )
+            .with_label(Label::new(attrib, "declared read-only here"))
+            .with_suggestion(Suggestion::new(
+                "remove the attribute to make the variable mutable",
+                attrib,
+                "",
+            ));
+
+            self.reports.push(error);
+        }
+    }
+
+    fn r_break(&mut self, node: &SyntaxNode) {
+        if self.current().loops > 0 {
+            return;
+        }
+
+        let span = self.span(node.text_range());
+        let error = Diagnostic::error(
+            Code::BreakOutsideLoop,
+            "break outside a loop",
+            Label::new(span, "there is no loop to break out of"),
+        );
+
+        self.reports.push(error);
+    }
+
+    fn vararg(&mut self, node: &SyntaxNode) {
+        if self.current().vararg {
+            return;
+        }
+
+        let span = self.span(node.text_range());
+        let error = Diagnostic::error(
+            Code::VarArgOutsideVarArgFunction,
+            "cannot use \"...\" outside a vararg function",
+            Label::new(span, "the enclosing function doesn't take \"...\""),
+        );
+
+        self.reports.push(error);
+    }
+
+    fn report_multiple_close(&mut self, name: Span, previous: Span) {
+        let error = Diagnostic::error(
+            Code::MultipleClose,
+            "multiple to-be-closed variables in local list",
+            Label::new(
+                name,
+                "only one variable in a local list may be to-be-closed",
+            ),
+        )
+        .with_label(Label::new(previous, "previously declared here"));
+
+        self.reports.push(error);
+    }
+
+    fn current(&mut self) -> &mut Function {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn span(&self, range: TextRange) -> Span {
+        Span::new(self.file, range.start().into(), range.end().into())
+    }
+}
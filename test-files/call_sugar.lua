@@ -0,0 +1,4 @@
+local mod = require "mod"
+class { name = "point" }
+print[[text]]
+obj:method 'arg'
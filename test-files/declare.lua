@@ -7,4 +7,5 @@ local w <close> = "abs"
 i, k = get(), "other1"
 local r, g = getf(), "other2"
 local f <const>, v <const> = getw(), "other3"
-local p <close>, o <close> = getq(), "other4"
+local p <close>, o <const> = getq(), "other4"
+local n < const > = 1